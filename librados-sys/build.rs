@@ -8,11 +8,20 @@ fn main() {
     // Tell cargo to tell rustc to link the system bzip2
     // shared library.
     println!("cargo:rustc-link-lib=rados");
+    let striper = env::var_os("CARGO_FEATURE_STRIPER").is_some();
+    if striper {
+        println!("cargo:rustc-link-lib=radosstriper");
+    }
 
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
-    let bindings = bindgen::Builder::default()
+    let mut builder = bindgen::Builder::default();
+    if striper {
+        // Pull libradosstriper.h in from wrapper.h.
+        builder = builder.clang_arg("-DWITH_RADOS_STRIPER");
+    }
+    let bindings = builder
         // The input header we would like to generate
         // bindings for.
         .header("wrapper.h")
@@ -1 +1,4 @@
-#include <rados/librados.h>
\ No newline at end of file
+#include <rados/librados.h>
+#ifdef WITH_RADOS_STRIPER
+#include <radosstriper/libradosstriper.h>
+#endif
@@ -0,0 +1,606 @@
+//! Async adapters for objects. `AsyncObjectReader` keeps a window of aio reads
+//! in flight ahead of the read position and `AsyncObjectWriter` lets a window
+//! of aio writes complete behind the writer. They implement the `tokio::io`
+//! traits with the `tokio` feature and the `futures::io` ones with the
+//! `futures-io` feature, and are only built with one of them.
+
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::future::Future;
+use std::io::{self, SeekFrom};
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use librados_sys::*;
+
+use crate::buffer::IO_BUF_SIZE;
+use crate::errors::{check_error, Error};
+use crate::io::{AioCompletion, IoCtx};
+use crate::object::{Object, ReadWrite};
+use crate::utils::seek_target;
+
+const DEFAULT_WINDOW: usize = 4;
+
+// An aio read with the buffer it reads into.
+struct PendingOp {
+    offset: u64,
+    buf: Vec<u8>,
+    comp: AioCompletion,
+}
+
+impl PendingOp {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.comp).poll(cx).map_err(io::Error::from)
+    }
+}
+
+impl Drop for PendingOp {
+    fn drop(&mut self) {
+        self.comp.wait_if_running();
+    }
+}
+
+struct PendingStat {
+    size: Box<u64>,
+    mtime: Box<time_t>,
+    comp: AioCompletion,
+}
+
+impl PendingStat {
+    fn start(io_ctx: &IoCtx, key: &CString) -> io::Result<Self> {
+        let mut stat = PendingStat {
+            size: Box::new(0),
+            mtime: Box::new(0),
+            comp: AioCompletion::new()?,
+        };
+        let code = unsafe {
+            rados_aio_stat(
+                io_ctx.ptr,
+                key.as_ptr(),
+                stat.comp.ptr,
+                stat.size.as_mut(),
+                stat.mtime.as_mut(),
+            )
+        };
+        check_error(code)?;
+        Ok(stat)
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        ready!(Pin::new(&mut self.comp).poll(cx))?;
+        Poll::Ready(Ok(*self.size))
+    }
+}
+
+impl Drop for PendingStat {
+    fn drop(&mut self) {
+        self.comp.wait_if_running();
+    }
+}
+
+enum SeekState {
+    Requested(SeekFrom),
+    // seeking relative to the end, waiting for the object size
+    Stat(i64, PendingStat),
+}
+
+// Drive a seek to the absolute position it targets, `None` if no seek is in
+// progress.
+fn poll_seek_target(
+    state: &mut Option<SeekState>,
+    io_ctx: &IoCtx,
+    key: &CString,
+    position: u64,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<Option<u64>>> {
+    loop {
+        let target = match state {
+            None => return Poll::Ready(Ok(None)),
+            Some(SeekState::Requested(SeekFrom::Start(offset))) => Ok(*offset),
+            Some(SeekState::Requested(SeekFrom::Current(offset))) => seek_target(position, *offset),
+            Some(SeekState::Requested(SeekFrom::End(offset))) => {
+                match PendingStat::start(io_ctx, key) {
+                    Ok(stat) => {
+                        *state = Some(SeekState::Stat(*offset, stat));
+                        continue;
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            Some(SeekState::Stat(offset, stat)) => {
+                let offset = *offset;
+                ready!(stat.poll(cx)).and_then(|size| seek_target(size, offset))
+            }
+        };
+        *state = None;
+        return Poll::Ready(target.map(Some));
+    }
+}
+
+fn seek_in_progress() -> io::Error {
+    io::Error::other("another seek is in progress")
+}
+
+/// Async reader over an object, or over an object at a snapshot, created by
+/// `Object::reader`. Up to `window` reads of `chunk_size` bytes are kept in
+/// flight ahead of the read position.
+pub struct AsyncObjectReader<'a, A = ReadWrite> {
+    io_ctx: IoCtx<'a>,
+    key: CString,
+    // completed read being handed out, starting at chunk_offset
+    chunk: Vec<u8>,
+    chunk_offset: u64,
+    chunk_pos: usize,
+    // reads in flight, contiguous from the end of `chunk`
+    ahead: VecDeque<PendingOp>,
+    next_offset: u64,
+    // a short read was seen, there is nothing to read past it
+    eof: bool,
+    // reads abandoned by a seek, kept until librados is done with them
+    retired: Vec<PendingOp>,
+    seek: Option<SeekState>,
+    window: usize,
+    chunk_size: usize,
+    // fn() keeps the reader Unpin whatever the access marker
+    _access: PhantomData<fn() -> A>,
+}
+
+impl<'a, A> AsyncObjectReader<'a, A> {
+    pub(crate) fn new(object: &Object<'a, A>) -> Result<Self, Error> {
+        Ok(AsyncObjectReader {
+            io_ctx: object.io_ctx()?,
+            key: CString::new(object.name())?,
+            chunk: Vec::new(),
+            chunk_offset: 0,
+            chunk_pos: 0,
+            ahead: VecDeque::new(),
+            next_offset: 0,
+            eof: false,
+            retired: Vec::new(),
+            seek: None,
+            window: DEFAULT_WINDOW,
+            chunk_size: IO_BUF_SIZE,
+            _access: PhantomData,
+        })
+    }
+
+    /// Keep up to `window` reads of `chunk_size` bytes in flight.
+    pub fn read_ahead(mut self, window: usize, chunk_size: usize) -> Self {
+        self.window = window.max(1);
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    pub fn position(&self) -> u64 {
+        self.chunk_offset + self.chunk_pos as u64
+    }
+
+    fn fill_window(&mut self) -> io::Result<()> {
+        while !self.eof && self.ahead.len() < self.window {
+            let mut buf = vec![0u8; self.chunk_size];
+            let comp = AioCompletion::new()?;
+            let code = unsafe {
+                rados_aio_read(
+                    self.io_ctx.ptr,
+                    self.key.as_ptr(),
+                    comp.ptr,
+                    buf.as_mut_ptr() as *mut c_char,
+                    buf.len(),
+                    self.next_offset,
+                )
+            };
+            check_error(code)?;
+            self.ahead.push_back(PendingOp {
+                offset: self.next_offset,
+                buf,
+                comp,
+            });
+            self.next_offset += self.chunk_size as u64;
+        }
+        Ok(())
+    }
+
+    fn poll_read_into(&mut self, cx: &mut Context<'_>, out: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.retired.retain(|op| !op.comp.is_complete_and_cb());
+
+        loop {
+            if self.chunk_pos < self.chunk.len() || out.is_empty() {
+                let available = &self.chunk[self.chunk_pos..];
+                let n = available.len().min(out.len());
+                out[..n].copy_from_slice(&available[..n]);
+                self.chunk_pos += n;
+                return Poll::Ready(Ok(n));
+            }
+
+            self.fill_window()?;
+            if self.ahead.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            let mut op = ready!(self.poll_oldest(cx))?;
+            if op.buf.len() < self.chunk_size {
+                self.eof = true;
+                self.retired.extend(self.ahead.drain(..));
+            }
+            self.chunk_offset = op.offset;
+            self.chunk = std::mem::take(&mut op.buf);
+            self.chunk_pos = 0;
+            if self.chunk.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+        }
+    }
+
+    // Take the oldest read once it completes. A failed read takes the rest of
+    // the window with it, so that reading again retries from where it failed.
+    fn poll_oldest(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<PendingOp>> {
+        let result = ready!(self.ahead.front_mut().expect("no pending read").poll(cx));
+        let mut op = self.ahead.pop_front().unwrap();
+        match result {
+            Ok(n) => {
+                op.buf.truncate(n);
+                Poll::Ready(Ok(op))
+            }
+            Err(e) => {
+                self.next_offset = op.offset;
+                self.retired.extend(self.ahead.drain(..));
+                Poll::Ready(Err(e))
+            }
+        }
+    }
+
+    fn start_seek(&mut self, pos: SeekFrom) -> io::Result<()> {
+        if self.seek.is_some() {
+            return Err(seek_in_progress());
+        }
+        self.seek = Some(SeekState::Requested(pos));
+        Ok(())
+    }
+
+    fn poll_seek(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        let position = self.position();
+        let target = match ready!(poll_seek_target(
+            &mut self.seek,
+            &self.io_ctx,
+            &self.key,
+            position,
+            cx
+        ))? {
+            Some(target) => target,
+            None => return Poll::Ready(Ok(position)),
+        };
+
+        let chunk_end = self.chunk_offset + self.chunk.len() as u64;
+        if target >= self.chunk_offset && target <= chunk_end {
+            self.chunk_pos = (target - self.chunk_offset) as usize;
+        } else {
+            self.retired.extend(self.ahead.drain(..));
+            self.chunk.clear();
+            self.chunk_offset = target;
+            self.chunk_pos = 0;
+            self.next_offset = target;
+            self.eof = false;
+        }
+        Poll::Ready(Ok(target))
+    }
+}
+
+/// Async writer over an object, created by `Object::writer`. Writes go to the
+/// object in place from offset 0 and are gathered into `chunk_size` bytes
+/// before being sent, with up to `window` writes in flight.
+///
+/// Shut the writer down (`poll_shutdown`, or `poll_close` with `futures-io`)
+/// before dropping it to see write errors. Dropping does no I/O: data still
+/// buffered is discarded and writes in flight complete unobserved.
+///
+/// On pools that require alignment (see `Pool::requires_alignment`) writes
+/// only land at the end of the object, so `chunk_size` is rounded up to the
+/// alignment and only a flush, seek or shutdown sends an unaligned tail. After
+/// that, like after a seek away from the end, writes fail with `EOPNOTSUPP`.
+pub struct AsyncObjectWriter<'a> {
+    io_ctx: IoCtx<'a>,
+    key: CString,
+    // object offset of buf[0]
+    buf_offset: u64,
+    buf: Vec<u8>,
+    // rados_aio_write copies the data, so only the completions are kept
+    behind: VecDeque<AioCompletion>,
+    seek: Option<SeekState>,
+    window: usize,
+    // a multiple of alignment when the pool requires one
+    chunk_size: usize,
+    alignment: usize,
+}
+
+impl<'a> AsyncObjectWriter<'a> {
+    pub(crate) fn new(object: &Object<'a>) -> Result<Self, Error> {
+        let alignment = object.alignment()? as usize;
+        Ok(AsyncObjectWriter {
+            io_ctx: object.io_ctx()?,
+            key: CString::new(object.name())?,
+            buf_offset: 0,
+            buf: Vec::new(),
+            behind: VecDeque::new(),
+            seek: None,
+            window: DEFAULT_WINDOW,
+            chunk_size: IO_BUF_SIZE.next_multiple_of(alignment.max(1)),
+            alignment,
+        })
+    }
+
+    /// Send writes of `chunk_size` bytes, rounded up to the pool alignment,
+    /// with up to `window` in flight.
+    pub fn write_behind(mut self, window: usize, chunk_size: usize) -> Self {
+        self.window = window.max(1);
+        self.chunk_size = chunk_size.max(1).next_multiple_of(self.alignment.max(1));
+        self
+    }
+
+    pub fn position(&self) -> u64 {
+        self.buf_offset + self.buf.len() as u64
+    }
+
+    // wait for the oldest write in flight
+    fn poll_oldest(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let comp = self.behind.front_mut().expect("no pending write");
+        let result = ready!(Pin::new(comp).poll(cx));
+        self.behind.pop_front();
+        Poll::Ready(result.map(|_| ()).map_err(io::Error::from))
+    }
+
+    // send the buffered data once the window has room
+    fn poll_submit(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.behind.len() >= self.window {
+            ready!(self.poll_oldest(cx))?;
+        }
+        if self.buf.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+
+        let comp = AioCompletion::new()?;
+        let code = unsafe {
+            rados_aio_write(
+                self.io_ctx.ptr,
+                self.key.as_ptr(),
+                comp.ptr,
+                self.buf.as_ptr() as *const c_char,
+                self.buf.len(),
+                self.buf_offset,
+            )
+        };
+        check_error(code)?;
+
+        self.buf_offset += self.buf.len() as u64;
+        self.buf.clear();
+        self.behind.push_back(comp);
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_write_from(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+        if self.buf.len() >= self.chunk_size {
+            ready!(self.poll_submit(cx))?;
+        }
+        let n = data.len().min(self.chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush_all(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_submit(cx))?;
+        while !self.behind.is_empty() {
+            ready!(self.poll_oldest(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_seek(&mut self, pos: SeekFrom) -> io::Result<()> {
+        if self.seek.is_some() {
+            return Err(seek_in_progress());
+        }
+        self.seek = Some(SeekState::Requested(pos));
+        Ok(())
+    }
+
+    fn poll_seek(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        if self.seek.is_none() {
+            return Poll::Ready(Ok(self.position()));
+        }
+        // everything written so far has to land first, seeking from the end
+        // must see it
+        ready!(self.poll_flush_all(cx))?;
+
+        let position = self.position();
+        if let Some(target) = ready!(poll_seek_target(
+            &mut self.seek,
+            &self.io_ctx,
+            &self.key,
+            position,
+            cx
+        ))? {
+            self.buf_offset = target;
+        }
+        Poll::Ready(Ok(self.buf_offset))
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_io {
+    use super::*;
+    use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+    impl<A> AsyncRead for AsyncObjectReader<'_, A> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let n = ready!(self.get_mut().poll_read_into(cx, buf.initialize_unfilled()))?;
+            buf.advance(n);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<A> AsyncSeek for AsyncObjectReader<'_, A> {
+        fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+            self.get_mut().start_seek(position)
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+            self.get_mut().poll_seek(cx)
+        }
+    }
+
+    impl AsyncWrite for AsyncObjectWriter<'_> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_from(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush_all(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush_all(cx)
+        }
+    }
+
+    impl AsyncSeek for AsyncObjectWriter<'_> {
+        fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+            self.get_mut().start_seek(position)
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+            self.get_mut().poll_seek(cx)
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_io {
+    use super::*;
+    use futures::io::{AsyncRead, AsyncSeek, AsyncWrite};
+
+    impl<A> AsyncRead for AsyncObjectReader<'_, A> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_read_into(cx, buf)
+        }
+    }
+
+    impl<A> AsyncSeek for AsyncObjectReader<'_, A> {
+        fn poll_seek(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            pos: SeekFrom,
+        ) -> Poll<io::Result<u64>> {
+            let this = self.get_mut();
+            if this.seek.is_none() {
+                this.start_seek(pos)?;
+            }
+            this.poll_seek(cx)
+        }
+    }
+
+    impl AsyncWrite for AsyncObjectWriter<'_> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_from(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush_all(cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush_all(cx)
+        }
+    }
+
+    impl AsyncSeek for AsyncObjectWriter<'_> {
+        fn poll_seek(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            pos: SeekFrom,
+        ) -> Poll<io::Result<u64>> {
+            let this = self.get_mut();
+            if this.seek.is_none() {
+                this.start_seek(pos)?;
+            }
+            this.poll_seek(cx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pool::Pool;
+    use crate::rados::Rados;
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_tokio_reader_writer() {
+        use std::io::SeekFrom;
+        use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let _ = pool.remove_object("async-io").await;
+        let obj = pool.create_object("async-io").await.unwrap();
+
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let mut writer = obj.writer().unwrap().write_behind(3, 4096);
+        tokio::io::copy(&mut &data[..], &mut writer).await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, data.len() as u64);
+
+        let mut reader = obj.reader().unwrap().read_ahead(3, 4096);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, data);
+
+        reader.seek(SeekFrom::End(-10)).await.unwrap();
+        let mut tail = Vec::new();
+        reader.read_to_end(&mut tail).await.unwrap();
+        assert_eq!(tail, &data[data.len() - 10..]);
+
+        reader.seek(SeekFrom::Start(5000)).await.unwrap();
+        let mut buf = [0u8; 100];
+        reader.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf[..], &data[5000..5100]);
+
+        pool.remove_object("async-io").await.unwrap();
+    }
+
+    #[cfg(feature = "futures-io")]
+    #[tokio::test]
+    async fn test_futures_reader_writer() {
+        use futures::io::{AsyncReadExt, AsyncWriteExt};
+
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let _ = pool.remove_object("async-io-futures").await;
+        let obj = pool.create_object("async-io-futures").await.unwrap();
+
+        let mut writer = obj.writer().unwrap().write_behind(2, 7);
+        writer.write_all(b"hello futures io").await.unwrap();
+        writer.close().await.unwrap();
+
+        let mut read = String::new();
+        let mut reader = obj.reader().unwrap().read_ahead(2, 5);
+        reader.read_to_string(&mut read).await.unwrap();
+        assert_eq!(read, "hello futures io");
+
+        pool.remove_object("async-io-futures").await.unwrap();
+    }
+}
@@ -1 +1,46 @@
+use std::os::raw::c_char;
+use std::slice;
+
+use bytes::Bytes;
+use librados_sys::rados_buffer_free;
+
 pub(crate) const MAX_BUF_SIZE: usize = 4 * 1024 * 1024;
+pub(crate) const XATTR_INITIAL_SIZE: usize = 4 * 1024;
+pub(crate) const IO_BUF_SIZE: usize = 1024 * 1024;
+
+// A reply buffer allocated by librados, freed with rados_buffer_free.
+struct RadosBuffer {
+    ptr: *mut c_char,
+    len: usize,
+}
+
+// plain heap memory handed over by librados, nothing else refers to it
+unsafe impl Send for RadosBuffer {}
+
+impl AsRef<[u8]> for RadosBuffer {
+    fn as_ref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for RadosBuffer {
+    fn drop(&mut self) {
+        unsafe { rados_buffer_free(self.ptr) }
+    }
+}
+
+/// Take over a buffer librados allocated, without copying it. A null `ptr`
+/// gives an empty buffer.
+///
+/// # Safety
+/// `ptr` must be null or point to `len` bytes to be freed with
+/// `rados_buffer_free`, and not be used by the caller afterwards.
+pub(crate) unsafe fn rados_buffer_to_bytes(ptr: *mut c_char, len: usize) -> Bytes {
+    if ptr.is_null() {
+        return Bytes::new();
+    }
+    Bytes::from_owner(RadosBuffer { ptr, len })
+}
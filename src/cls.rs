@@ -0,0 +1,10 @@
+//! Typed wrappers for object classes that ship with ceph. They call the class
+//! methods through `Object::exec` and `Object::exec_write`, encoding requests
+//! the way the OSD side expects.
+
+mod encoding;
+
+pub mod lock;
+pub mod log;
+pub mod refcount;
+pub mod version;
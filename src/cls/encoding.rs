@@ -0,0 +1,224 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+
+use crate::errors::Error;
+
+/// Little endian encoder for the payloads object classes exchange, following
+/// ceph's `encode`/`ENCODE_START` layout.
+#[derive(Debug, Default)]
+pub(crate) struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn into_vec(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) -> &mut Self {
+        self.u8(value as u8)
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn i32(&mut self, value: i32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Strings and bufferlists share the same u32 length prefixed layout.
+    pub(crate) fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub(crate) fn string(&mut self, value: &str) -> &mut Self {
+        self.bytes(value.as_bytes())
+    }
+
+    /// `utime_t`: seconds and nanoseconds since the epoch, both u32.
+    pub(crate) fn utime(&mut self, value: SystemTime) -> &mut Self {
+        let since = value.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.u32(since.as_secs() as u32).u32(since.subsec_nanos())
+    }
+
+    /// A relative `utime_t`, as used for lock durations.
+    pub(crate) fn duration(&mut self, value: Duration) -> &mut Self {
+        self.u32(value.as_secs() as u32).u32(value.subsec_nanos())
+    }
+
+    pub(crate) fn list<T>(&mut self, items: &[T], f: impl Fn(&mut Self, &T)) -> &mut Self {
+        self.u32(items.len() as u32);
+        for item in items {
+            f(self, item);
+        }
+        self
+    }
+
+    /// A versioned struct: version, compat version and the length of the
+    /// payload written by `f`.
+    pub(crate) fn versioned(
+        &mut self,
+        version: u8,
+        compat: u8,
+        f: impl FnOnce(&mut Self),
+    ) -> &mut Self {
+        self.u8(version).u8(compat);
+        let len_at = self.buf.len();
+        self.u32(0);
+        f(self);
+        let len = (self.buf.len() - len_at - 4) as u32;
+        self.buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+        self
+    }
+}
+
+/// Counterpart of `Encoder` for class method replies.
+#[derive(Debug)]
+pub(crate) struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Decoder { buf }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.buf.len() < len {
+            return Err(Error::OtherError(anyhow!(
+                "truncated object class reply, needed {} more bytes",
+                len - self.buf.len()
+            )));
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        Ok(std::str::from_utf8(self.take(len)?)?.to_string())
+    }
+
+    pub(crate) fn utime(&mut self) -> Result<SystemTime, Error> {
+        let secs = self.u32()? as u64;
+        let nanos = self.u32()?;
+        Ok(UNIX_EPOCH + Duration::new(secs, nanos))
+    }
+
+    pub(crate) fn list<T>(
+        &mut self,
+        f: impl Fn(&mut Self) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let count = self.u32()? as usize;
+        (0..count).map(|_| f(self)).collect()
+    }
+
+    /// Decode a versioned struct with `f`, which gets the struct version.
+    /// Fields appended by newer versions that `f` doesn't read are skipped.
+    pub(crate) fn versioned<T>(
+        &mut self,
+        f: impl FnOnce(&mut Decoder<'a>, u8) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let version = self.u8()?;
+        let _compat = self.u8()?;
+        let len = self.u32()? as usize;
+        let mut inner = Decoder::new(self.take(len)?);
+        f(&mut inner, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versioned_roundtrip() {
+        let stamp = UNIX_EPOCH + Duration::new(1_700_000_000, 42);
+        let mut enc = Encoder::new();
+        enc.versioned(2, 1, |enc| {
+            enc.string("name").u64(7).utime(stamp);
+            enc.list(&["a", "b"], |enc, s| {
+                enc.string(s);
+            });
+        })
+        .bool(true);
+        let buf = enc.into_vec();
+        // v, compat, le32 payload length
+        assert_eq!(&buf[..6], &[2, 1, 38, 0, 0, 0]);
+
+        let mut dec = Decoder::new(&buf);
+        let (name, value, time, list) = dec
+            .versioned(|dec, version| {
+                assert_eq!(version, 2);
+                Ok((
+                    dec.string()?,
+                    dec.u64()?,
+                    dec.utime()?,
+                    dec.list(|dec| dec.string())?,
+                ))
+            })
+            .unwrap();
+        assert_eq!(name, "name");
+        assert_eq!(value, 7);
+        assert_eq!(time, stamp);
+        assert_eq!(list, vec!["a", "b"]);
+        assert!(dec.bool().unwrap());
+        assert!(dec.u8().is_err());
+    }
+
+    #[test]
+    fn test_versioned_skips_unknown_fields() {
+        let mut enc = Encoder::new();
+        enc.versioned(3, 1, |enc| {
+            enc.u32(1).string("added in v3");
+        })
+        .u8(9);
+        let buf = enc.into_vec();
+
+        let mut dec = Decoder::new(&buf);
+        assert_eq!(dec.versioned(|dec, _| dec.u32()).unwrap(), 1);
+        assert_eq!(dec.u8().unwrap(), 9);
+    }
+}
@@ -0,0 +1,134 @@
+//! Advisory locks on objects, `cls_lock`.
+
+use std::time::Duration;
+
+use crate::cls::encoding::{Decoder, Encoder};
+use crate::errors::Error;
+use crate::object::Object;
+
+const CLASS: &str = "lock";
+
+const LOCK_EXCLUSIVE: u8 = 1;
+const LOCK_SHARED: u8 = 2;
+
+const LOCK_FLAG_MAY_RENEW: u8 = 1;
+
+/// A named lock on an object, held by `cookie`.
+///
+/// `lock` fails with `EBUSY` while another holder has the lock and with
+/// `EEXIST` if this holder already has it, unless `renew` is set.
+#[derive(Debug, Clone)]
+pub struct Lock {
+    name: String,
+    shared: bool,
+    cookie: String,
+    tag: String,
+    description: String,
+    duration: Option<Duration>,
+    renew: bool,
+}
+
+impl Lock {
+    pub fn exclusive(name: &str, cookie: &str) -> Self {
+        Lock {
+            name: name.to_string(),
+            shared: false,
+            cookie: cookie.to_string(),
+            tag: String::new(),
+            description: String::new(),
+            duration: None,
+            renew: false,
+        }
+    }
+
+    /// A lock that any number of holders using the same `tag` can share.
+    pub fn shared(name: &str, cookie: &str, tag: &str) -> Self {
+        Lock {
+            shared: true,
+            tag: tag.to_string(),
+            ..Self::exclusive(name, cookie)
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// Let the lock expire after `duration` instead of holding it until unlocked.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Take the lock even if this holder already has it, restarting its duration.
+    pub fn renew(mut self, renew: bool) -> Self {
+        self.renew = renew;
+        self
+    }
+
+    pub async fn lock(&self, object: &Object<'_>) -> Result<(), Error> {
+        let lock_type = if self.shared {
+            LOCK_SHARED
+        } else {
+            LOCK_EXCLUSIVE
+        };
+        let mut input = Encoder::new();
+        input.versioned(1, 1, |enc| {
+            enc.string(&self.name)
+                .u8(lock_type)
+                .string(&self.cookie)
+                .string(&self.tag)
+                .string(&self.description)
+                .duration(self.duration.unwrap_or_default())
+                .u8(if self.renew { LOCK_FLAG_MAY_RENEW } else { 0 });
+        });
+        object.exec_write(CLASS, "lock", &input.into_vec()).await?;
+        Ok(())
+    }
+
+    pub async fn unlock(&self, object: &Object<'_>) -> Result<(), Error> {
+        let mut input = Encoder::new();
+        input.versioned(1, 1, |enc| {
+            enc.string(&self.name).string(&self.cookie);
+        });
+        object
+            .exec_write(CLASS, "unlock", &input.into_vec())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Names of the locks currently held on `object`.
+pub async fn list_locks<A>(object: &Object<'_, A>) -> Result<Vec<String>, Error> {
+    let reply = object.exec_bytes(CLASS, "list_locks", &[]).await?;
+    Decoder::new(&reply).versioned(|dec, _| dec.list(|dec| dec.string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::check_error;
+    use crate::pool::Pool;
+    use crate::rados::Rados;
+
+    #[tokio::test]
+    async fn test_lock() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool.put_object("cls-lock", b"data").await.unwrap();
+
+        let lock = Lock::exclusive("owner", "cookie1").description("test lock");
+        lock.lock(&obj).await.unwrap();
+        assert_eq!(list_locks(&obj).await.unwrap(), vec!["owner"]);
+
+        let busy = Lock::exclusive("owner", "cookie2").lock(&obj).await;
+        assert_eq!(busy.unwrap_err(), check_error(-16).unwrap_err());
+        lock.clone().renew(true).lock(&obj).await.unwrap();
+
+        lock.unlock(&obj).await.unwrap();
+        assert!(list_locks(&obj).await.unwrap().is_empty());
+
+        pool.remove_object("cls-lock").await.unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+//! Time ordered logs kept in an object's omap, `cls_log`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cls::encoding::{Decoder, Encoder};
+use crate::errors::{check_error, Error};
+use crate::object::Object;
+
+const CLASS: &str = "log";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Assigned by the OSD from the timestamp, ignored by `add`.
+    pub id: String,
+    pub section: String,
+    pub name: String,
+    pub timestamp: SystemTime,
+    pub data: Vec<u8>,
+}
+
+impl LogEntry {
+    pub fn new(section: &str, name: &str, timestamp: SystemTime, data: &[u8]) -> Self {
+        LogEntry {
+            id: String::new(),
+            section: section.to_string(),
+            name: name.to_string(),
+            timestamp,
+            data: data.to_vec(),
+        }
+    }
+
+    fn encode(&self, enc: &mut Encoder) {
+        enc.versioned(2, 1, |enc| {
+            enc.string(&self.section)
+                .string(&self.name)
+                .utime(self.timestamp)
+                .bytes(&self.data)
+                .string(&self.id);
+        });
+    }
+
+    fn decode(dec: &mut Decoder) -> Result<Self, Error> {
+        dec.versioned(|dec, version| {
+            let mut entry = LogEntry {
+                id: String::new(),
+                section: dec.string()?,
+                name: dec.string()?,
+                timestamp: dec.utime()?,
+                data: dec.bytes()?,
+            };
+            if version >= 2 {
+                entry.id = dec.string()?;
+            }
+            Ok(entry)
+        })
+    }
+}
+
+/// One page of entries returned by `list`.
+#[derive(Debug)]
+pub struct LogListing {
+    pub entries: Vec<LogEntry>,
+    /// Pass to the next `list` call to continue after the last entry.
+    pub marker: String,
+    pub truncated: bool,
+}
+
+pub async fn add(object: &Object<'_>, entries: &[LogEntry]) -> Result<(), Error> {
+    let mut input = Encoder::new();
+    input.versioned(2, 1, |enc| {
+        enc.list(entries, |enc, entry| entry.encode(enc))
+            .bool(false);
+    });
+    object.exec_write(CLASS, "add", &input.into_vec()).await?;
+    Ok(())
+}
+
+/// List up to `max` entries starting at `marker`, or at `from` if `marker` is
+/// empty. `to` bounds the listing only when `from` is given.
+pub async fn list<A>(
+    object: &Object<'_, A>,
+    from: Option<SystemTime>,
+    to: Option<SystemTime>,
+    marker: &str,
+    max: usize,
+) -> Result<LogListing, Error> {
+    let mut input = Encoder::new();
+    input.versioned(1, 1, |enc| {
+        enc.utime(from.unwrap_or(UNIX_EPOCH))
+            .string(marker)
+            .utime(to.unwrap_or(UNIX_EPOCH))
+            .i32(max.min(i32::MAX as usize) as i32);
+    });
+    let reply = object.exec_bytes(CLASS, "list", &input.into_vec()).await?;
+
+    Decoder::new(&reply).versioned(|dec, _| {
+        Ok(LogListing {
+            entries: dec.list(LogEntry::decode)?,
+            marker: dec.string()?,
+            truncated: dec.bool()?,
+        })
+    })
+}
+
+/// Remove entries timestamped between `from` and `to`. The OSD trims a
+/// bounded number of entries per call; returns false once there was nothing
+/// left to trim.
+pub async fn trim(object: &Object<'_>, from: SystemTime, to: SystemTime) -> Result<bool, Error> {
+    let mut input = Encoder::new();
+    input.versioned(2, 1, |enc| {
+        enc.utime(from).utime(to).string("").string("");
+    });
+
+    match object.exec_write(CLASS, "trim", &input.into_vec()).await {
+        Ok(_) => Ok(true),
+        Err(e) if e == check_error(-61).unwrap_err() => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::pool::Pool;
+    use crate::rados::Rados;
+
+    #[tokio::test]
+    async fn test_log() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let _ = pool.remove_object("cls-log").await;
+        let obj = pool.create_object("cls-log").await.unwrap();
+
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let entries: Vec<_> = (0..5)
+            .map(|i| {
+                LogEntry::new(
+                    "section",
+                    &format!("entry{}", i),
+                    start + Duration::from_secs(i),
+                    b"data",
+                )
+            })
+            .collect();
+        add(&obj, &entries).await.unwrap();
+
+        let page = list(&obj, None, None, "", 3).await.unwrap();
+        assert_eq!(page.entries.len(), 3);
+        assert!(page.truncated);
+        assert_eq!(page.entries[0].name, "entry0");
+        assert_eq!(page.entries[0].data, b"data");
+        assert!(!page.entries[0].id.is_empty());
+
+        let rest = list(&obj, None, None, &page.marker, 3).await.unwrap();
+        assert_eq!(rest.entries.len(), 2);
+        assert!(!rest.truncated);
+
+        while trim(&obj, start, start + Duration::from_secs(10))
+            .await
+            .unwrap()
+        {}
+        assert!(list(&obj, None, None, "", 10)
+            .await
+            .unwrap()
+            .entries
+            .is_empty());
+
+        pool.remove_object("cls-log").await.unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+//! Reference counted objects, `cls_refcount`. Each reference is a tag; the
+//! object is removed when the last one is put.
+
+use crate::cls::encoding::{Decoder, Encoder};
+use crate::errors::Error;
+use crate::object::Object;
+
+const CLASS: &str = "refcount";
+
+fn tag_op(tag: &str, implicit_ref: bool) -> Vec<u8> {
+    let mut input = Encoder::new();
+    input.versioned(1, 1, |enc| {
+        enc.string(tag).bool(implicit_ref);
+    });
+    input.into_vec()
+}
+
+/// Take a reference named `tag`. With `implicit_ref` an object that has
+/// never been refcounted starts out with one unnamed reference.
+pub async fn get(object: &Object<'_>, tag: &str, implicit_ref: bool) -> Result<(), Error> {
+    object
+        .exec_write(CLASS, "get", &tag_op(tag, implicit_ref))
+        .await?;
+    Ok(())
+}
+
+/// Drop the reference named `tag`, removing the object if it was the last.
+pub async fn put(object: &Object<'_>, tag: &str, implicit_ref: bool) -> Result<(), Error> {
+    object
+        .exec_write(CLASS, "put", &tag_op(tag, implicit_ref))
+        .await?;
+    Ok(())
+}
+
+/// Tags of the references currently held.
+pub async fn read<A>(object: &Object<'_, A>, implicit_ref: bool) -> Result<Vec<String>, Error> {
+    let mut input = Encoder::new();
+    input.versioned(1, 1, |enc| {
+        enc.bool(implicit_ref);
+    });
+    let reply = object.exec_bytes(CLASS, "read", &input.into_vec()).await?;
+    Decoder::new(&reply).versioned(|dec, _| dec.list(|dec| dec.string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::Pool;
+    use crate::rados::Rados;
+
+    #[tokio::test]
+    async fn test_refcount() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool.put_object("cls-refcount", b"data").await.unwrap();
+
+        get(&obj, "a", false).await.unwrap();
+        get(&obj, "b", false).await.unwrap();
+        let mut refs = read(&obj, false).await.unwrap();
+        refs.sort();
+        assert_eq!(refs, vec!["a", "b"]);
+
+        put(&obj, "a", false).await.unwrap();
+        assert!(obj.exists().await.unwrap());
+        put(&obj, "b", false).await.unwrap();
+        assert!(!obj.exists().await.unwrap());
+    }
+}
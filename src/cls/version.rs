@@ -0,0 +1,83 @@
+//! Object versions kept by `cls_version`, independent of the RADOS object
+//! version and shared by all writers that go through the class.
+
+use crate::cls::encoding::{Decoder, Encoder};
+use crate::errors::Error;
+use crate::object::Object;
+
+const CLASS: &str = "version";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjVersion {
+    pub ver: u64,
+    pub tag: String,
+}
+
+impl ObjVersion {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.versioned(1, 1, |enc| {
+            enc.u64(self.ver).string(&self.tag);
+        });
+    }
+
+    fn decode(dec: &mut Decoder) -> Result<Self, Error> {
+        dec.versioned(|dec, _| {
+            Ok(ObjVersion {
+                ver: dec.u64()?,
+                tag: dec.string()?,
+            })
+        })
+    }
+}
+
+pub async fn set(object: &Object<'_>, version: &ObjVersion) -> Result<(), Error> {
+    let mut input = Encoder::new();
+    input.versioned(1, 1, |enc| version.encode(enc));
+    object.exec_write(CLASS, "set", &input.into_vec()).await?;
+    Ok(())
+}
+
+/// Bump the version, creating it with a random tag if the object has none.
+pub async fn inc(object: &Object<'_>) -> Result<(), Error> {
+    let mut input = Encoder::new();
+    input.versioned(1, 1, |enc| {
+        ObjVersion::default().encode(enc);
+        // no conditions
+        enc.u32(0);
+    });
+    object.exec_write(CLASS, "inc", &input.into_vec()).await?;
+    Ok(())
+}
+
+pub async fn read<A>(object: &Object<'_, A>) -> Result<ObjVersion, Error> {
+    let reply = object.exec_bytes(CLASS, "read", &[]).await?;
+    Decoder::new(&reply).versioned(|dec, _| ObjVersion::decode(dec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::Pool;
+    use crate::rados::Rados;
+
+    #[tokio::test]
+    async fn test_version() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool.put_object("cls-version", b"data").await.unwrap();
+
+        let version = ObjVersion {
+            ver: 5,
+            tag: "tag".to_string(),
+        };
+        set(&obj, &version).await.unwrap();
+        assert_eq!(read(&obj).await.unwrap(), version);
+
+        inc(&obj).await.unwrap();
+        let bumped = read(&obj).await.unwrap();
+        assert_eq!(bumped.ver, 6);
+        assert_eq!(bumped.tag, "tag");
+
+        pool.remove_object("cls-version").await.unwrap();
+    }
+}
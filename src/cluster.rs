@@ -1,12 +1,18 @@
 use std::ffi::{c_char, c_int, CString};
-use std::io::{BufRead, Cursor};
 use std::sync::Arc;
 
-use librados_sys::{rados_pool_create, rados_pool_delete, rados_pool_list, rados_pool_lookup};
+use bytes::Bytes;
+use librados_sys::{
+    rados_pool_create, rados_pool_create_with_all, rados_pool_create_with_auid,
+    rados_pool_create_with_crush_rule, rados_pool_delete, rados_pool_get_base_tier,
+    rados_pool_list, rados_pool_lookup,
+};
+use serde_json::json;
 
-use crate::errors::{check_error, Error};
-use crate::pool::Pool;
+use crate::errors::{check_error, Error, ERROR_EXISTS, ERROR_NOT_FOUND};
+use crate::pool::{Application, Pool, PoolInfo};
 use crate::rados::Rados;
+use crate::utils::split_nul_terminated;
 
 #[derive(Debug)]
 pub struct Cluster {
@@ -29,13 +35,121 @@ impl Cluster {
     }
 
     pub fn pool_create(&self, pool_name: &str) -> Result<Pool, Error> {
-        let pool_name = CString::new(pool_name)?;
-        unsafe {
-            let code = rados_pool_create(self.rados.ptr, pool_name.as_ptr());
-            check_error(code)?;
+        self.pool_create_with(pool_name, &PoolCreateOptions::default())
+    }
+
+    /// Create a pool and apply `options` to it.
+    ///
+    /// Returns `Error::AlreadyExists` if a pool with the same name exists.
+    ///
+    /// This is not atomic: the pool is created first, then configured one
+    /// setting and one application at a time, so other clients can see it
+    /// half-configured. If a step after the creation itself fails (setting the
+    /// size, enabling an application, ...) the pool is deleted again. That
+    /// needs `mon_allow_pool_delete`; if the delete fails too,
+    /// `Error::RollbackFailed` carries both errors and the pool is left
+    /// behind.
+    pub fn pool_create_with(
+        &self,
+        pool_name: &str,
+        options: &PoolCreateOptions,
+    ) -> Result<Pool, Error> {
+        let name = CString::new(pool_name)?;
+        // fast path only, the create below reports a pool created concurrently
+        if unsafe { rados_pool_lookup(self.rados.ptr, name.as_ptr()) } >= 0 {
+            return Err(Error::AlreadyExists(pool_name.to_string()));
         }
 
-        self.pool_lookup(pool_name.to_str()?)
+        if options.needs_mon_command() {
+            let mut cmd = json!({
+                "prefix": "osd pool create",
+                "pool": pool_name,
+                "format": "json",
+            });
+            if let Some(pg_num) = options.pg_num {
+                cmd["pg_num"] = json!(pg_num);
+            }
+            if let Some(profile) = &options.erasure_code_profile {
+                cmd["pool_type"] = json!("erasure");
+                cmd["erasure_code_profile"] = json!(profile);
+            }
+            // `osd pool create` takes the rule by name
+            if let Some(rule) = options.crush_rule {
+                cmd["rule"] = json!(self.crush_rule_name(rule)?);
+            }
+            // the monitors report success for a pool that exists already
+            let (_, status) = self.mon_command_bytes(&cmd.to_string(), &[])?;
+            if status.contains("already exists") {
+                return Err(Error::AlreadyExists(pool_name.to_string()));
+            }
+        } else {
+            let code = unsafe {
+                match (options.auid, options.crush_rule) {
+                    (None, None) => rados_pool_create(self.rados.ptr, name.as_ptr()),
+                    (Some(auid), None) => {
+                        rados_pool_create_with_auid(self.rados.ptr, name.as_ptr(), auid)
+                    }
+                    (None, Some(rule)) => {
+                        rados_pool_create_with_crush_rule(self.rados.ptr, name.as_ptr(), rule)
+                    }
+                    (Some(auid), Some(rule)) => {
+                        rados_pool_create_with_all(self.rados.ptr, name.as_ptr(), auid, rule)
+                    }
+                }
+            };
+            check_error(code).map_err(|e| {
+                if e == *ERROR_EXISTS {
+                    Error::AlreadyExists(pool_name.to_string())
+                } else {
+                    e
+                }
+            })?;
+        }
+
+        if let Err(e) = self.pool_configure(pool_name, options) {
+            return match self.pool_delete(pool_name) {
+                Ok(()) => Err(e),
+                Err(rollback) => Err(Error::RollbackFailed(Box::new(e), Box::new(rollback))),
+            };
+        }
+
+        self.pool_lookup(pool_name)
+    }
+
+    fn pool_configure(&self, pool_name: &str, options: &PoolCreateOptions) -> Result<(), Error> {
+        if let Some(size) = options.size {
+            let cmd = json!({
+                "prefix": "osd pool set",
+                "pool": pool_name,
+                "var": "size",
+                "val": size.to_string(),
+            });
+            self.mon_command_bytes(&cmd.to_string(), &[])?;
+        }
+
+        let pool = Pool::new(&self.rados, pool_name);
+        for application in &options.applications {
+            pool.application_enable(application, true)?;
+        }
+
+        Ok(())
+    }
+
+    // name of the crush rule with id `rule`, ENOENT if there is none
+    fn crush_rule_name(&self, rule: u8) -> Result<String, Error> {
+        let cmd = json!({
+            "prefix": "osd crush rule dump",
+            "format": "json",
+        });
+        let (output, _) = self.mon_command_bytes(&cmd.to_string(), &[])?;
+        let rules: Vec<serde_json::Value> =
+            serde_json::from_slice(&output).map_err(|e| Error::OtherError(e.into()))?;
+        rules
+            .iter()
+            .find(|r| r["rule_id"].as_u64() == Some(rule as u64))
+            .and_then(|r| r["rule_name"].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| check_error(-2).unwrap_err())
     }
 
     pub fn pool_delete(&self, pool_name: &str) -> Result<(), Error> {
@@ -44,53 +158,257 @@ impl Cluster {
         check_error(code)
     }
 
-    pub fn pool_list(&self) -> Result<Vec<Pool>, Error> {
-        let mut pools: Vec<Pool> = Vec::new();
-        let mut pool_buffer: Vec<u8> = Vec::with_capacity(500);
-        unsafe {
-            let rados = self.rados.clone();
+    /// Send a command to the monitors, returning the output buffer and status string.
+    pub fn mon_command(&self, cmd: &str, input: &[u8]) -> Result<(Vec<u8>, String), Error> {
+        let (output, status) = self.mon_command_bytes(cmd, input)?;
+        Ok((output.to_vec(), status))
+    }
 
-            let len = rados_pool_list(
-                rados.ptr,
-                pool_buffer.as_mut_ptr() as *mut c_char,
-                pool_buffer.capacity(),
-            );
+    /// Like `mon_command`, handing the output back in the buffer librados
+    /// allocated for it instead of copying it.
+    pub fn mon_command_bytes(&self, cmd: &str, input: &[u8]) -> Result<(Bytes, String), Error> {
+        self.rados.mon_command(cmd, input)
+    }
 
-            if len < 0 {
-                check_error(len)?;
+    /// Pools in the cluster. Pools deleted while they are being listed are
+    /// left out.
+    pub fn pool_list(&self) -> Result<Vec<PoolInfo>, Error> {
+        let mut pools = Vec::new();
+        for name in self.pool_names()? {
+            match self.pool_info(name) {
+                Ok(info) => pools.push(info),
+                // the pool was deleted after it was listed
+                Err(e) if e == *ERROR_NOT_FOUND => continue,
+                Err(e) => return Err(e),
             }
+        }
+
+        Ok(pools)
+    }
+
+    fn pool_info(&self, name: String) -> Result<PoolInfo, Error> {
+        let pool_name = CString::new(name.as_str())?;
+        let id = unsafe { rados_pool_lookup(self.rados.ptr, pool_name.as_ptr()) };
+        check_error(id as c_int)?;
+
+        let mut base_tier: i64 = 0;
+        let code = unsafe { rados_pool_get_base_tier(self.rados.ptr, id, &mut base_tier) };
+        check_error(code)?;
+
+        let applications = Pool::new(&self.rados, &name).application_list()?;
+        Ok(PoolInfo {
+            id,
+            name,
+            base_tier,
+            applications,
+        })
+    }
 
-            if len > pool_buffer.capacity() as i32 {
-                pool_buffer.reserve(len as usize);
-                let len = rados_pool_list(
-                    rados.ptr,
+    fn pool_names(&self) -> Result<Vec<String>, Error> {
+        let mut len: usize = 512;
+        loop {
+            let mut pool_buffer: Vec<u8> = vec![0; len];
+            let needed = unsafe {
+                rados_pool_list(
+                    self.rados.ptr,
                     pool_buffer.as_mut_ptr() as *mut c_char,
-                    pool_buffer.capacity(),
-                );
-                check_error(len as i32)?;
-                pool_buffer.set_len(len as usize);
-            } else {
-                pool_buffer.set_len(len as usize);
+                    pool_buffer.len(),
+                )
+            };
+            check_error(needed)?;
+
+            // the pool set may grow between calls, so retry until everything fits
+            let needed = needed as usize;
+            if needed <= pool_buffer.len() {
+                pool_buffer.truncate(needed);
+                return Ok(parse_pool_list(&pool_buffer));
             }
+            len = needed;
         }
+    }
+}
 
-        let mut cursor = Cursor::new(&pool_buffer);
-        loop {
-            let mut string_buf: Vec<u8> = Vec::new();
-            let read = cursor.read_until(0x00, &mut string_buf)?;
-            if read == 0 || read == 1 {
-                break;
-            } else {
-                pools.push(Pool::new(
-                    &self.rados,
-                    &String::from_utf8_lossy(&string_buf[..read - 1]),
-                ));
-            }
+/// Parse the buffer filled in by `rados_pool_list`: every name is NUL terminated
+/// and the list itself ends with an extra NUL.
+fn parse_pool_list(buf: &[u8]) -> Vec<String> {
+    let buf = buf.strip_suffix(&[0]).unwrap_or(buf);
+    split_nul_terminated(buf)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PoolCreateOptions {
+    crush_rule: Option<u8>,
+    auid: Option<u64>,
+    pg_num: Option<u32>,
+    size: Option<u32>,
+    erasure_code_profile: Option<String>,
+    applications: Vec<Application>,
+}
+
+impl PoolCreateOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Crush rule id. Looked up by name when the pool has to be created
+    /// through the monitors, failing with `ENOENT` if there is no such rule.
+    pub fn crush_rule(mut self, rule: u8) -> Self {
+        self.crush_rule = Some(rule);
+        self
+    }
+
+    /// Owner id of the pool. Ignored by Ceph releases since Nautilus, and not
+    /// applied when the pool has to be created through the monitors.
+    pub fn auid(mut self, auid: u64) -> Self {
+        self.auid = Some(auid);
+        self
+    }
+
+    pub fn pg_num(mut self, pg_num: u32) -> Self {
+        self.pg_num = Some(pg_num);
+        self
+    }
+
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn erasure_code_profile(mut self, profile: &str) -> Self {
+        self.erasure_code_profile = Some(profile.to_string());
+        self
+    }
+
+    pub fn application(mut self, application: Application) -> Self {
+        if !self.applications.contains(&application) {
+            self.applications.push(application);
         }
+        self
+    }
 
-        Ok(pools)
+    fn needs_mon_command(&self) -> bool {
+        self.pg_num.is_some() || self.erasure_code_profile.is_some()
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_create_with_options() {
+        let cluster = Cluster::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let _ = cluster.pool_delete("test-create-options");
+
+        let options = PoolCreateOptions::new()
+            .pg_num(8)
+            .size(1)
+            .application(Application::Rbd);
+        let pool = cluster
+            .pool_create_with("test-create-options", &options)
+            .unwrap();
+        assert_eq!(pool.name(), "test-create-options");
+
+        assert_eq!(
+            cluster.pool_create("test-create-options").unwrap_err(),
+            Error::AlreadyExists("test-create-options".to_string())
+        );
+
+        cluster.pool_delete("test-create-options").unwrap();
+    }
+
+    #[test]
+    fn test_pool_create_with_crush_rule() {
+        let cluster = Cluster::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let _ = cluster.pool_delete("test-create-rule");
+
+        // pg_num sends the create through the monitors, which take rule names
+        let options = PoolCreateOptions::new().pg_num(8).size(1).crush_rule(0);
+        cluster
+            .pool_create_with("test-create-rule", &options)
+            .unwrap();
+
+        let cmd = json!({
+            "prefix": "osd pool get",
+            "pool": "test-create-rule",
+            "var": "crush_rule",
+            "format": "json",
+        });
+        let (output, _) = cluster.mon_command(&cmd.to_string(), &[]).unwrap();
+        let rule: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(rule["crush_rule"], cluster.crush_rule_name(0).unwrap());
+        cluster.pool_delete("test-create-rule").unwrap();
+
+        let options = PoolCreateOptions::new().pg_num(8).crush_rule(u8::MAX);
+        assert_eq!(
+            cluster
+                .pool_create_with("test-create-rule", &options)
+                .unwrap_err(),
+            *ERROR_NOT_FOUND
+        );
+        assert!(cluster.pool_lookup("test-create-rule").is_err());
+    }
+
+    #[test]
+    fn test_pool_list() {
+        let cluster = Cluster::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+
+        let pools = cluster.pool_list().unwrap();
+        let test = pools.iter().find(|pool| pool.name == "test").unwrap();
+        assert!(test.id >= 0);
+        println!("{:?}", pools);
+    }
+
+    #[test]
+    fn test_pool_list_many() {
+        let cluster = Cluster::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        // far more than fit the initial list buffer; one PG each to stay
+        // within the per-OSD PG limit
+        let options = PoolCreateOptions::new().pg_num(1).size(1);
+        let names: Vec<String> = (0..200)
+            .map(|i| match i % 3 {
+                0 => format!("test-list-{}", i),
+                1 => format!("test list with spaces {}", i),
+                _ => format!("test-list-池-ñ-{}", i),
+            })
+            .collect();
+        for name in &names {
+            let _ = cluster.pool_delete(name);
+            cluster.pool_create_with(name, &options).unwrap();
+        }
+
+        let pools = cluster.pool_list().unwrap();
+        for name in &names {
+            assert!(pools.iter().any(|pool| &pool.name == name), "{}", name);
+        }
+
+        for name in &names {
+            cluster.pool_delete(name).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_pool_list() {
+        assert!(parse_pool_list(&[]).is_empty());
+        assert!(parse_pool_list(&[0]).is_empty());
+
+        let names: Vec<String> = (0..500)
+            .map(|i| match i % 5 {
+                0 => format!("pool-{}", i),
+                1 => format!("pool with spaces {}", i),
+                2 => format!("池-{}-ñ", i),
+                3 => format!(".rgw.root.{}", i),
+                _ => format!("{}!@#$%^&*()[]{{}}'\"", i),
+            })
+            .collect();
+
+        let mut buf = Vec::new();
+        for name in &names {
+            buf.extend_from_slice(name.as_bytes());
+            buf.push(0);
+        }
+        buf.push(0);
+
+        assert_eq!(parse_pool_list(&buf), names);
+    }
+}
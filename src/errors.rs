@@ -12,6 +12,20 @@ pub enum Error {
     NulError(std::ffi::NulError),
     IoError(std::io::Error),
     Utf8Error(std::str::Utf8Error),
+    AlreadyExists(String),
+    /// A write made conditional on this object version found another one.
+    VersionMismatch(u64),
+    /// A compare-and-write found different data, starting at this offset.
+    CompareMismatch(u64),
+    /// A conditional xattr update found the named xattr failing the comparison.
+    XattrMismatch(String),
+    /// The named xattr is larger than the limit set with `set_max_xattr_size`.
+    XattrTooLarge(String),
+    /// The pool only takes appends in multiples of this alignment.
+    Unaligned(u64),
+    /// An operation failed (first) and undoing its partial effect failed too
+    /// (second).
+    RollbackFailed(Box<Error>, Box<Error>),
     OtherError(anyhow::Error),
 }
 
@@ -24,6 +38,19 @@ impl Display for Error {
             Error::NulError(error) => write!(f, "NulError: {}", error),
             Error::IoError(error) => write!(f, "IoError: {}", error),
             Error::Utf8Error(error) => write!(f, "Utf8Error: {}", error),
+            Error::AlreadyExists(name) => write!(f, "AlreadyExists: {}", name),
+            Error::VersionMismatch(version) => {
+                write!(f, "VersionMismatch: expected version {}", version)
+            }
+            Error::CompareMismatch(offset) => write!(f, "CompareMismatch: at offset {}", offset),
+            Error::XattrMismatch(name) => write!(f, "XattrMismatch: {}", name),
+            Error::XattrTooLarge(name) => write!(f, "XattrTooLarge: {}", name),
+            Error::Unaligned(alignment) => {
+                write!(f, "Unaligned: pool requires multiples of {}", alignment)
+            }
+            Error::RollbackFailed(error, rollback) => {
+                write!(f, "RollbackFailed: {}, rollback: {}", error, rollback)
+            }
             Error::OtherError(message) => write!(f, "OtherError: {}", message),
         }
     }
@@ -48,6 +75,13 @@ impl std::error::Error for Error {
             Error::NulError(e) => e.source(),
             Error::IoError(e) => e.source(),
             Error::Utf8Error(e) => e.source(),
+            Error::AlreadyExists(_) => None,
+            Error::VersionMismatch(_) => None,
+            Error::CompareMismatch(_) => None,
+            Error::XattrMismatch(_) => None,
+            Error::XattrTooLarge(_) => None,
+            Error::Unaligned(_) => None,
+            Error::RollbackFailed(error, _) => Some(error.as_ref()),
             Error::OtherError(e) => e.source(),
         }
     }
@@ -77,14 +111,30 @@ impl From<std::str::Utf8Error> for Error {
     }
 }
 
+// largest errno, cmpext encodes mismatch offsets below it
+pub(crate) const MAX_ERRNO: isize = 4095;
+// what getxattr reports for a missing xattr
+pub(crate) const ENODATA: c_int = 61;
+
 lazy_static! {
     pub(crate) static ref ERROR_RANGE: Error = check_error(-34).unwrap_err();
+    pub(crate) static ref ERROR_NOT_FOUND: Error = check_error(-2).unwrap_err();
+    pub(crate) static ref ERROR_EXISTS: Error = check_error(-17).unwrap_err();
+    pub(crate) static ref ERROR_OVERFLOW: Error = check_error(-75).unwrap_err();
+    pub(crate) static ref ERROR_CANCELED: Error = check_error(-125).unwrap_err();
 }
 
 impl PartialEq for Error {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Error::RadosError((code1, _)), Error::RadosError((code2, _))) => code1 == code2,
+            (Error::AlreadyExists(name1), Error::AlreadyExists(name2)) => name1 == name2,
+            (Error::VersionMismatch(v1), Error::VersionMismatch(v2)) => v1 == v2,
+            (Error::CompareMismatch(o1), Error::CompareMismatch(o2)) => o1 == o2,
+            (Error::XattrMismatch(n1), Error::XattrMismatch(n2)) => n1 == n2,
+            (Error::XattrTooLarge(n1), Error::XattrTooLarge(n2)) => n1 == n2,
+            (Error::Unaligned(a1), Error::Unaligned(a2)) => a1 == a2,
+            (Error::RollbackFailed(e1, r1), Error::RollbackFailed(e2, r2)) => e1 == e2 && r1 == r2,
             _ => false,
         }
     }
@@ -1,15 +1,17 @@
+use std::ffi::CString;
 use std::future::Future;
 use std::marker::PhantomData;
 use std::mem::transmute;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicPtr, Ordering};
 use std::sync::Arc;
-use std::task::{Context, Poll, Waker};
+use std::task::{ready, Context, Poll, Waker};
 
 use librados_sys::*;
 
 use crate::errors::{check_error, Error};
-use crate::rados::Rados;
+use crate::rados::{DetachedRados, Rados};
+use crate::snapshot::{SnapContext, SnapId};
 
 #[derive(Debug)]
 pub(crate) struct IoCtx<'a> {
@@ -17,12 +19,26 @@ pub(crate) struct IoCtx<'a> {
     _marker: PhantomData<&'a ()>,
 }
 
+// io contexts are thread safe for I/O; the crate configures each one
+// (namespace, snapshots) before sharing it with an op
+unsafe impl Send for IoCtx<'_> {}
+unsafe impl Sync for IoCtx<'_> {}
+
 impl<'a> IoCtx<'a> {
     pub(crate) fn new(rados: &'a Rados, pool_name: String) -> Result<Self, Error> {
+        Self::create(rados.ptr, pool_name)
+    }
+
+    /// An io context for a blocking call, see `Rados::detach`.
+    pub(crate) fn detached(rados: &'a DetachedRados, pool_name: String) -> Result<Self, Error> {
+        Self::create(rados.ptr, pool_name)
+    }
+
+    fn create(cluster: rados_t, pool_name: String) -> Result<Self, Error> {
         let mut ptr = std::ptr::null_mut();
         let pool_name = std::ffi::CString::new(pool_name)?;
 
-        let code = unsafe { rados_ioctx_create(rados.ptr, pool_name.as_ptr(), &mut ptr) };
+        let code = unsafe { rados_ioctx_create(cluster, pool_name.as_ptr(), &mut ptr) };
         check_error(code)?;
 
         Ok(IoCtx {
@@ -30,6 +46,32 @@ impl<'a> IoCtx<'a> {
             _marker: PhantomData,
         })
     }
+
+    /// Serve subsequent reads from `snap` instead of the head object.
+    pub(crate) fn set_read_snap(&self, snap: SnapId) {
+        unsafe { rados_ioctx_snap_set_read(self.ptr, snap.id()) }
+    }
+
+    /// Address objects in `namespace` instead of the default one.
+    pub(crate) fn set_namespace(&self, namespace: &str) -> Result<(), Error> {
+        let namespace = CString::new(namespace)?;
+        unsafe { rados_ioctx_set_namespace(self.ptr, namespace.as_ptr()) };
+        Ok(())
+    }
+
+    /// Tag subsequent writes with a self-managed snapshot context.
+    pub(crate) fn set_write_ctx(&self, ctx: &SnapContext) -> Result<(), Error> {
+        let mut snaps: Vec<u64> = ctx.snaps().iter().map(SnapId::id).collect();
+        let code = unsafe {
+            rados_ioctx_selfmanaged_snap_set_write_ctx(
+                self.ptr,
+                ctx.seq().id(),
+                snaps.as_mut_ptr(),
+                snaps.len() as i32,
+            )
+        };
+        check_error(code)
+    }
 }
 
 impl Drop for IoCtx<'_> {
@@ -46,6 +88,11 @@ pub(crate) struct AioCompletion {
     waker: Arc<Box<AtomicPtr<Waker>>>,
 }
 
+// completions are thread safe, librados already completes them on its own
+// threads
+unsafe impl Send for AioCompletion {}
+unsafe impl Sync for AioCompletion {}
+
 impl AioCompletion {
     pub(crate) fn new() -> Result<Self, Error> {
         let mut comp = AioCompletion {
@@ -85,6 +132,11 @@ impl AioCompletion {
         Ok(ret as usize)
     }
 
+    /// Version of the object the completed operation acted on.
+    pub(crate) fn version(&self) -> u64 {
+        unsafe { rados_aio_get_version(self.ptr) }
+    }
+
     #[allow(dead_code)]
     pub(crate) fn wait_for_complete(&self) -> Result<usize, Error> {
         let ret = unsafe { rados_aio_get_return_value(self.ptr) };
@@ -94,12 +146,10 @@ impl AioCompletion {
         Ok(ret as usize)
     }
 
-    #[allow(dead_code)]
     pub(crate) fn is_complete_and_cb(&self) -> bool {
         unsafe { rados_aio_is_complete_and_cb(self.ptr) != 0 }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn wait_for_complete_and_cb(&self) -> Result<usize, Error> {
         let ret = unsafe {
             // the code is always 0
@@ -111,6 +161,14 @@ impl AioCompletion {
         check_error(ret)?;
         Ok(ret as usize)
     }
+
+    /// Wait for the op if librados may still be filling or reading from its
+    /// buffers, so they can be freed.
+    pub(crate) fn wait_if_running(&self) {
+        if !self.is_complete_and_cb() {
+            let _ = self.wait_for_complete_and_cb();
+        }
+    }
 }
 
 impl Drop for AioCompletion {
@@ -139,6 +197,157 @@ impl Future for AioCompletion {
     }
 }
 
+/// An aio op that owns the buffer librados reads from or writes into. The
+/// buffer is handed back once the op completes; if the op is dropped before
+/// that, it waits for librados to be done with the buffer.
+pub(crate) struct BufferedCompletion<B> {
+    comp: AioCompletion,
+    buf: Option<B>,
+}
+
+impl<B> BufferedCompletion<B> {
+    pub(crate) fn new(comp: AioCompletion, buf: B) -> Self {
+        BufferedCompletion {
+            comp,
+            buf: Some(buf),
+        }
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.comp.version()
+    }
+}
+
+impl<B: Unpin> Future for BufferedCompletion<B> {
+    type Output = Result<(usize, B), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ret = ready!(Pin::new(&mut self.comp).poll(cx));
+        let buf = self.buf.take().expect("polled after completion");
+        Poll::Ready(ret.map(|n| (n, buf)))
+    }
+}
+
+impl<B> Drop for BufferedCompletion<B> {
+    fn drop(&mut self) {
+        if self.buf.is_some() {
+            self.comp.wait_if_running();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct WriteOp {
+    pub(crate) ptr: rados_write_op_t,
+}
+
+// ops are built and run by one task; librados doesn't tie them to a thread
+unsafe impl Send for WriteOp {}
+unsafe impl Sync for WriteOp {}
+
+impl WriteOp {
+    pub(crate) fn new() -> Result<Self, Error> {
+        let ptr = unsafe { rados_create_write_op() };
+        if ptr.is_null() {
+            return Err(Error::OtherError(anyhow::anyhow!(
+                "failed to allocate write op"
+            )));
+        }
+        Ok(WriteOp { ptr })
+    }
+
+    /// Run the op, returning its result along with the object version.
+    pub(crate) async fn operate(
+        &self,
+        io_ctx: &IoCtx<'_>,
+        oid: &str,
+    ) -> Result<(usize, u64), Error> {
+        let mut comp = AioCompletion::new()?;
+        let oid = CString::new(oid)?;
+
+        let code = unsafe {
+            rados_aio_write_op_operate(
+                self.ptr,
+                io_ctx.ptr,
+                comp.ptr,
+                oid.as_ptr(),
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        let ret = (&mut comp).await?;
+        Ok((ret, comp.version()))
+    }
+}
+
+impl Drop for WriteOp {
+    fn drop(&mut self) {
+        unsafe { rados_release_write_op(self.ptr) }
+    }
+}
+
+pub(crate) struct ReadOp {
+    pub(crate) ptr: rados_read_op_t,
+}
+
+unsafe impl Send for ReadOp {}
+unsafe impl Sync for ReadOp {}
+
+impl ReadOp {
+    pub(crate) fn new() -> Result<Self, Error> {
+        let ptr = unsafe { rados_create_read_op() };
+        if ptr.is_null() {
+            return Err(Error::OtherError(anyhow::anyhow!(
+                "failed to allocate read op"
+            )));
+        }
+        Ok(ReadOp { ptr })
+    }
+
+    /// Run the op, returning its result along with the object version.
+    pub(crate) async fn operate(
+        &self,
+        io_ctx: &IoCtx<'_>,
+        oid: &str,
+    ) -> Result<(usize, u64), Error> {
+        let mut comp = AioCompletion::new()?;
+        let oid = CString::new(oid)?;
+
+        let code =
+            unsafe { rados_aio_read_op_operate(self.ptr, io_ctx.ptr, comp.ptr, oid.as_ptr(), 0) };
+        assert!(code <= 0);
+        check_error(code)?;
+        let ret = (&mut comp).await?;
+        Ok((ret, comp.version()))
+    }
+
+    /// Start the op, handing `buf`, which owns whatever the op's out-params
+    /// point into, to the returned completion, see `BufferedCompletion`.
+    pub(crate) fn operate_buffered<B>(
+        &self,
+        io_ctx: &IoCtx<'_>,
+        oid: &str,
+        buf: B,
+    ) -> Result<BufferedCompletion<B>, Error> {
+        let comp = AioCompletion::new()?;
+        let oid = CString::new(oid)?;
+
+        let code =
+            unsafe { rados_aio_read_op_operate(self.ptr, io_ctx.ptr, comp.ptr, oid.as_ptr(), 0) };
+        assert!(code <= 0);
+        check_error(code)?;
+        Ok(BufferedCompletion::new(comp, buf))
+    }
+}
+
+impl Drop for ReadOp {
+    fn drop(&mut self) {
+        unsafe { rados_release_read_op(self.ptr) }
+    }
+}
+
 unsafe extern "C" fn aio_callback(
     _completion: rados_completion_t,
     _arg: *mut std::os::raw::c_void,
@@ -1,7 +1,13 @@
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub mod async_io;
+pub mod cls;
 pub mod cluster;
 pub mod errors;
 pub mod object;
 pub mod pool;
+pub mod snapshot;
+pub mod striper;
+pub mod sync_io;
 pub mod xattr;
 
 mod buffer;
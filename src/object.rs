@@ -1,38 +1,125 @@
 use std::fmt::Debug;
-use std::ops::DerefMut;
-use std::os::raw::c_char;
+use std::marker::PhantomData;
+use std::ops::{Bound, DerefMut, RangeBounds};
+use std::os::raw::{c_char, c_int};
+use std::sync::OnceLock;
 
+use bitflags::bitflags;
+use bytes::{Bytes, BytesMut};
 use librados_sys::*;
+use serde::de::DeserializeOwned;
 
-use crate::buffer::MAX_BUF_SIZE;
-use crate::errors::{check_error, Error, ERROR_RANGE};
-use crate::io::{AioCompletion, IoCtx};
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+use crate::async_io::{AsyncObjectReader, AsyncObjectWriter};
+use crate::buffer::{rados_buffer_to_bytes, IO_BUF_SIZE, MAX_BUF_SIZE, XATTR_INITIAL_SIZE};
+use crate::errors::{
+    check_error, Error, ERROR_CANCELED, ERROR_EXISTS, ERROR_NOT_FOUND, ERROR_OVERFLOW, ERROR_RANGE,
+    MAX_ERRNO,
+};
+use crate::io::{AioCompletion, BufferedCompletion, IoCtx, ReadOp, WriteOp};
+use crate::pool::Pool;
 use crate::rados::Rados;
-use crate::xattr::{RadosXattrsIter, Xattrs};
+use crate::snapshot::{SnapContext, SnapId, SnapVersion};
+use crate::xattr::{get_xattr_capped, CmpXattrOp, RadosXattrsIter, Xattrs};
 
+/// Marker for objects that can be read and written.
 #[derive(Debug)]
-pub struct Object<'a> {
+pub struct ReadWrite;
+
+/// Marker for objects that can only be read, such as objects seen through a
+/// pool snapshot.
+#[derive(Debug)]
+pub struct ReadOnly;
+
+#[derive(Debug)]
+pub struct Object<'a, A = ReadWrite> {
     name: String,
     pool_name: String,
+    // empty for the default namespace
+    namespace: String,
     rados: &'a Rados,
+    // snapshot reads are served from, SnapId::HEAD for the live object
+    snap: SnapId,
+    snap_context: Option<SnapContext>,
+    max_xattr_size: usize,
+    // 0 when the pool accepts unaligned appends
+    alignment: OnceLock<u64>,
+    _access: PhantomData<A>,
 }
 
-impl<'a> Object<'a> {
-    pub(crate) fn new(rados: &'a Rados, pool_name: &str, name: &str) -> Self {
+// await the write `comp`, returning the object version it produced
+async fn complete(mut comp: AioCompletion) -> Result<WriteResult, Error> {
+    (&mut comp).await?;
+    Ok(WriteResult {
+        version: comp.version(),
+    })
+}
+
+// like `complete`, for an op that owns its buffer
+async fn complete_owned<B: Unpin>(mut op: BufferedCompletion<B>) -> Result<WriteResult, Error> {
+    (&mut op).await?;
+    Ok(WriteResult {
+        version: op.version(),
+    })
+}
+
+impl<'a, A> Object<'a, A> {
+    pub(crate) fn new_at(rados: &'a Rados, pool_name: &str, name: &str, snap: SnapId) -> Self {
         Object {
             name: name.to_string(),
             pool_name: pool_name.to_string(),
+            namespace: String::new(),
             rados,
+            snap,
+            snap_context: None,
+            max_xattr_size: usize::MAX,
+            alignment: OnceLock::new(),
+            _access: PhantomData,
         }
     }
 
+    pub(crate) fn with_snap_context(mut self, ctx: Option<SnapContext>) -> Self {
+        self.snap_context = ctx;
+        self
+    }
+
+    pub(crate) fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    pub(crate) fn io_ctx(&self) -> Result<IoCtx<'a>, Error> {
+        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
+        if !self.namespace.is_empty() {
+            io_ctx.set_namespace(&self.namespace)?;
+        }
+        if !self.snap.is_head() {
+            io_ctx.set_read_snap(self.snap);
+        }
+        if let Some(ctx) = &self.snap_context {
+            io_ctx.set_write_ctx(ctx)?;
+        }
+        Ok(io_ctx)
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Namespace of the object, empty for the default one.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
     pub async fn stat(&self) -> Result<Stat, Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
-        let comp = AioCompletion::new()?;
+        let (stat, _) = self.stat_with_version().await?;
+        Ok(stat)
+    }
+
+    /// `stat`, together with the object version, see `if_version`.
+    pub async fn stat_with_version(&self) -> Result<(Stat, u64), Error> {
+        let io_ctx = self.io_ctx()?;
+        let mut comp = AioCompletion::new()?;
         let key = std::ffi::CString::new(self.name())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
@@ -53,21 +140,33 @@ impl<'a> Object<'a> {
         };
         assert!(code <= 0);
         check_error(code)?;
-        comp.await?;
+        (&mut comp).await?;
 
-        Ok(Stat {
+        let stat = Stat {
             size: *psize,
             mtime: (pmtime.tv_sec as u64, pmtime.tv_nsec as u64),
-        })
+        };
+        Ok((stat, comp.version()))
     }
 
     pub async fn read(&self, pos: u64, buf: &mut [u8]) -> Result<usize, Error> {
         if buf.is_empty() {
             return Ok(0);
         }
+        let (read, _) = self.read_with_version(pos, buf).await?;
+        Ok(read)
+    }
 
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
-        let comp = AioCompletion::new()?;
+    /// `read`, together with the object version the data belongs to, see
+    /// `if_version`. An empty `buf` costs a stat, for the version.
+    pub async fn read_with_version(&self, pos: u64, buf: &mut [u8]) -> Result<(usize, u64), Error> {
+        if buf.is_empty() {
+            let (_, version) = self.stat_with_version().await?;
+            return Ok((0, version));
+        }
+
+        let io_ctx = self.io_ctx()?;
+        let mut comp = AioCompletion::new()?;
         let key = std::ffi::CString::new(self.name())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
 
@@ -84,11 +183,713 @@ impl<'a> Object<'a> {
         assert!(code <= 0);
         check_error(code)?;
 
-        comp.await
+        let read = (&mut comp).await?;
+        Ok((read, comp.version()))
     }
 
-    pub async fn write(&self, pos: u64, buf: &[u8]) -> Result<usize, Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
+    /// Read at `pos` into the spare capacity of `buf`, appending to it
+    /// without zeroing or copying. The op owns `buf` until it completes, so
+    /// the buffer stays valid even if the returned future is dropped early.
+    pub async fn read_buf(&self, pos: u64, buf: BytesMut) -> Result<BytesMut, Error> {
+        if buf.capacity() == buf.len() {
+            return Ok(buf);
+        }
+        let (buf, _) = self.read_buf_with_version(pos, buf).await?;
+        Ok(buf)
+    }
+
+    /// `read_buf`, together with the object version the data belongs to, see
+    /// `if_version`. A full `buf` costs a stat, for the version.
+    pub async fn read_buf_with_version(
+        &self,
+        pos: u64,
+        mut buf: BytesMut,
+    ) -> Result<(BytesMut, u64), Error> {
+        let spare = buf.spare_capacity_mut();
+        if spare.is_empty() {
+            let (_, version) = self.stat_with_version().await?;
+            return Ok((buf, version));
+        }
+        let (ptr, len) = (spare.as_mut_ptr() as *mut c_char, spare.len());
+
+        let io_ctx = self.io_ctx()?;
+        let comp = AioCompletion::new()?;
+        let key = std::ffi::CString::new(self.name())?;
+
+        let code = unsafe {
+            rados_aio_read(
+                io_ctx.ptr,
+                key.as_ptr() as *mut c_char,
+                comp.ptr,
+                ptr,
+                len,
+                pos,
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+
+        let mut comp = BufferedCompletion::new(comp, buf);
+        let (read, mut buf) = (&mut comp).await?;
+        unsafe { buf.set_len(buf.len() + read) };
+        Ok((buf, comp.version()))
+    }
+
+    /// Read up to `len` bytes at `pos` into a new buffer, see `read_buf`.
+    pub async fn read_bytes(&self, pos: u64, len: usize) -> Result<Bytes, Error> {
+        let buf = self.read_buf(pos, BytesMut::with_capacity(len)).await?;
+        Ok(buf.freeze())
+    }
+
+    /// `read_bytes`, together with the object version the data belongs to,
+    /// see `read_buf_with_version`.
+    pub async fn read_bytes_with_version(
+        &self,
+        pos: u64,
+        len: usize,
+    ) -> Result<(Bytes, u64), Error> {
+        let (buf, version) = self
+            .read_buf_with_version(pos, BytesMut::with_capacity(len))
+            .await?;
+        Ok((buf.freeze(), version))
+    }
+
+    /// Read several extents, given as `(offset, len)`, in one compound op and
+    /// so in one round trip. Each extent gets its own result: its data, cut
+    /// short at the end of the object, or the error the OSD returned for it,
+    /// which doesn't keep the other extents from being read. The outer error
+    /// is for the op as a whole, such as a missing object.
+    ///
+    /// The op owns the buffers until it completes, as with `read_buf`.
+    pub async fn read_vectored(
+        &self,
+        extents: &[(u64, usize)],
+    ) -> Result<Vec<Result<Bytes, Error>>, Error> {
+        if extents.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (results, _) = self.read_vectored_with_version(extents).await?;
+        Ok(results)
+    }
+
+    /// `read_vectored`, together with the object version the extents were
+    /// read from, see `if_version`. No extents cost a stat, for the version.
+    pub async fn read_vectored_with_version(
+        &self,
+        extents: &[(u64, usize)],
+    ) -> Result<(Vec<Result<Bytes, Error>>, u64), Error> {
+        if extents.is_empty() {
+            let (_, version) = self.stat_with_version().await?;
+            return Ok((Vec::new(), version));
+        }
+        let mut bufs: Vec<BytesMut> = extents
+            .iter()
+            .map(|&(_, len)| BytesMut::with_capacity(len))
+            .collect();
+        let mut bytes_read = vec![0usize; extents.len()];
+        let mut prvals: Vec<c_int> = vec![0; extents.len()];
+
+        let io_ctx = self.io_ctx()?;
+        let op = ReadOp::new()?;
+        for (i, (&(offset, len), buf)) in extents.iter().zip(&mut bufs).enumerate() {
+            // a zero length read is a read of the whole object to the OSD
+            if len == 0 {
+                continue;
+            }
+            unsafe {
+                rados_read_op_read(
+                    op.ptr,
+                    offset,
+                    len,
+                    buf.spare_capacity_mut().as_mut_ptr() as *mut c_char,
+                    bytes_read.as_mut_ptr().add(i),
+                    prvals.as_mut_ptr().add(i),
+                );
+                // a failed extent only fails its own result
+                rados_read_op_set_flags(op.ptr, LIBRADOS_OP_FLAG_FAILOK as c_int);
+            }
+        }
+
+        let mut comp = op.operate_buffered(&io_ctx, self.name(), (bufs, bytes_read, prvals))?;
+        let (_, (bufs, bytes_read, prvals)) = (&mut comp).await?;
+
+        let results = bufs
+            .into_iter()
+            .zip(bytes_read)
+            .zip(prvals)
+            .map(|((mut buf, read), prval)| {
+                check_error(prval)?;
+                unsafe { buf.set_len(read) };
+                Ok(buf.freeze())
+            })
+            .collect();
+        Ok((results, comp.version()))
+    }
+
+    /// Read the whole object, see `read_range`.
+    pub async fn read_to_end(&self) -> Result<Bytes, Error> {
+        self.read_range(..).await
+    }
+
+    /// Read `range` of the object, clamped to its size, see `read_versioned`.
+    pub async fn read_range(&self, range: impl RangeBounds<u64>) -> Result<Bytes, Error> {
+        let (data, _) = self.read_versioned(range).await?;
+        Ok(data)
+    }
+
+    /// Read `range` of the object, clamped to its size, together with the
+    /// object version the data belongs to. Pass the version to `if_version`
+    /// to make a later write conditional on the object not having changed.
+    ///
+    /// The object is stat'ed in the same op as the first read, and reads are
+    /// repeated until the range is covered. Follow-up reads are made
+    /// conditional on the version the first one saw, so the result never mixes
+    /// data from before and after a concurrent write; the read starts over
+    /// if the object changes, and fails with `Error::VersionMismatch` if it
+    /// keeps changing.
+    pub async fn read_versioned(
+        &self,
+        range: impl RangeBounds<u64>,
+    ) -> Result<(Bytes, u64), Error> {
+        const ATTEMPTS: usize = 3;
+
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        // past u64::MAX is past any object size
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1),
+            Bound::Excluded(&end) => Some(end),
+            Bound::Unbounded => None,
+        };
+        // the object size is only known after the first read, so that one is
+        // capped, and kept from running past u64::MAX
+        let first_len = end
+            .unwrap_or(u64::MAX)
+            .saturating_sub(start)
+            .min(IO_BUF_SIZE as u64) as usize;
+
+        let mut version = 0;
+        for _ in 0..ATTEMPTS {
+            let (mut data, size, seen) = self.read_op(start, first_len, None).await?;
+            version = seen;
+            let end = end.map_or(size, |end| end.min(size));
+
+            let mut pos = start + data.len() as u64;
+            data.reserve(end.saturating_sub(pos) as usize);
+            let mut changed = false;
+            while pos < end {
+                let len = (end - pos).min(MAX_BUF_SIZE as u64) as usize;
+                match self.read_op(pos, len, Some(version)).await {
+                    Ok((chunk, _, _)) if chunk.is_empty() => break,
+                    Ok((chunk, _, _)) => {
+                        pos += chunk.len() as u64;
+                        data.extend_from_slice(&chunk);
+                    }
+                    Err(Error::VersionMismatch(_)) => {
+                        changed = true;
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if !changed {
+                data.truncate(end.saturating_sub(start) as usize);
+                return Ok((Bytes::from(data), version));
+            }
+        }
+        Err(Error::VersionMismatch(version))
+    }
+
+    // Read `len` bytes at `offset` together with a stat of the object, only
+    // if the object is still at `version` when one is given.
+    async fn read_op(
+        &self,
+        offset: u64,
+        len: usize,
+        version: Option<u64>,
+    ) -> Result<(Vec<u8>, u64, u64), Error> {
+        let mut buf = vec![0u8; len];
+        let mut bytes_read = Box::new(0usize);
+        let mut size = Box::new(0u64);
+        let mut mtime: Box<time_t> = Box::new(0);
+
+        let io_ctx = self.io_ctx()?;
+        let op = ReadOp::new()?;
+        unsafe {
+            // snapshots are immutable, there's no version to hold them to
+            if let (Some(version), true) = (version, self.snap.is_head()) {
+                rados_read_op_assert_version(op.ptr, version);
+            }
+            rados_read_op_stat(op.ptr, size.as_mut(), mtime.as_mut(), std::ptr::null_mut());
+            // a zero length read is a read of the whole object to the OSD
+            if len > 0 {
+                rados_read_op_read(
+                    op.ptr,
+                    offset,
+                    buf.len(),
+                    buf.as_mut_ptr() as *mut c_char,
+                    bytes_read.as_mut(),
+                    std::ptr::null_mut(),
+                );
+            }
+        }
+
+        let (_, seen) = match op.operate(&io_ctx, self.name()).await {
+            Err(e) if version.is_some() && (e == *ERROR_RANGE || e == *ERROR_OVERFLOW) => {
+                return Err(Error::VersionMismatch(version.unwrap()));
+            }
+            result => result?,
+        };
+        buf.truncate(*bytes_read);
+        Ok((buf, *size, seen))
+    }
+
+    /// Get an xattr value.
+    ///
+    /// The value is first read into a 4 KiB buffer. If it doesn't fit, one
+    /// more round trip fetches it at its exact size, see `set_max_xattr_size`.
+    pub async fn get_xattr(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let (value, _) = self.get_xattr_with_version(name).await?;
+        Ok(value)
+    }
+
+    /// `get_xattr`, together with the object version the value belongs to,
+    /// see `if_version`.
+    pub async fn get_xattr_with_version(&self, name: &str) -> Result<(Vec<u8>, u64), Error> {
+        get_xattr_capped(
+            name,
+            self.max_xattr_size,
+            |buf| self.getxattr_into(name, buf),
+            || self.get_xattrs_with_version(),
+        )
+        .await
+    }
+
+    // read xattr `name` into `buf`, which must be large enough for it
+    async fn getxattr_into(&self, name: &str, mut buf: Vec<u8>) -> Result<(Vec<u8>, u64), Error> {
+        let io_ctx = self.io_ctx()?;
+        let key = std::ffi::CString::new(self.name())?;
+        let name = std::ffi::CString::new(name)?;
+
+        let mut comp = AioCompletion::new()?;
+        let code = unsafe {
+            rados_aio_getxattr(
+                io_ctx.ptr,
+                key.as_ptr() as *mut c_char,
+                comp.ptr,
+                name.as_ptr() as *const c_char,
+                buf.as_mut_ptr() as *mut c_char,
+                buf.len(),
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        let size = (&mut comp).await?;
+        buf.truncate(size);
+        Ok((buf, comp.version()))
+    }
+
+    /// Limit the size of xattr values `get_xattr` accepts, unlimited by
+    /// default. Larger values fail with `Error::XattrTooLarge`. Up to 4 MiB,
+    /// the limit also spares fetching every xattr to learn a value's size.
+    pub fn set_max_xattr_size(&mut self, size: usize) {
+        self.max_xattr_size = size;
+    }
+
+    /// Get an xattr and decode it as UTF-8.
+    pub async fn get_xattr_str(&self, name: &str) -> Result<String, Error> {
+        let value = self.get_xattr(name).await?;
+        String::from_utf8(value).map_err(|e| e.utf8_error().into())
+    }
+
+    /// Get an xattr and deserialize it from JSON.
+    pub async fn get_xattr_as<T: DeserializeOwned>(&self, name: &str) -> Result<T, Error> {
+        let value = self.get_xattr(name).await?;
+        serde_json::from_slice(&value).map_err(|e| Error::OtherError(e.into()))
+    }
+
+    pub async fn get_xattrs(&self) -> Result<Xattrs, Error> {
+        let (xattrs, _) = self.get_xattrs_with_version().await?;
+        Ok(xattrs)
+    }
+
+    /// `get_xattrs`, together with the object version they belong to, see
+    /// `if_version`.
+    pub async fn get_xattrs_with_version(&self) -> Result<(Xattrs, u64), Error> {
+        let io_ctx = self.io_ctx()?;
+        let key = std::ffi::CString::new(self.name())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        let mut comp = AioCompletion::new()?;
+        let mut iter = RadosXattrsIter::new(std::ptr::null_mut());
+
+        let code = unsafe {
+            rados_aio_getxattrs(
+                io_ctx.ptr,
+                key.as_ptr() as *mut c_char,
+                comp.ptr,
+                &mut iter.ptr,
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        (&mut comp).await?;
+
+        let xattrs = Xattrs::from_iter(&iter)?;
+
+        Ok((xattrs, comp.version()))
+    }
+
+    /// The distinct versions of the object seen by the pool snapshots that
+    /// include it, oldest first, excluding the one still at the head.
+    ///
+    /// This is not a clone listing. The C API has no counterpart of the C++
+    /// `list_snaps` op, so versions are inferred by stat'ing the object at
+    /// every pool snapshot: consecutive snapshots seeing the same size and
+    /// mtime are taken to share one version. Self-managed snapshots aren't
+    /// visible this way, and neither is the overlap of a clone with the head,
+    /// so the sizes overstate the space the clones use.
+    pub async fn snap_versions(&self) -> Result<Vec<SnapVersion>, Error> {
+        let mut snap_ids = Pool::new(self.rados, &self.pool_name)
+            .snapshot_list()
+            .await?;
+        snap_ids.sort();
+
+        let head = self.at(SnapId::HEAD).stat_if_exists().await?;
+        let mut versions: Vec<SnapVersion> = Vec::new();
+        let mut last: Option<Stat> = None;
+
+        for snap_id in snap_ids {
+            let stat = self.at(snap_id).stat_if_exists().await?;
+            match (stat, last, versions.last_mut()) {
+                (None, _, _) => {}
+                (Some(stat), Some(prev), Some(version)) if stat == prev => {
+                    version.snaps.push(snap_id);
+                }
+                (Some(stat), _, _) => versions.push(SnapVersion {
+                    snaps: vec![snap_id],
+                    size: stat.size,
+                }),
+            }
+            last = stat;
+        }
+
+        if last.is_some() && last == head {
+            versions.pop();
+        }
+        Ok(versions)
+    }
+
+    // read-only handle to the same object as of `snap`
+    fn at(&self, snap: SnapId) -> Object<'a, ReadOnly> {
+        Object::new_at(self.rados, &self.pool_name, &self.name, snap)
+            .with_namespace(&self.namespace)
+    }
+
+    /// Checksum `len` bytes starting at `offset` on the OSD, one value per
+    /// `chunk_size` bytes (a single value if `chunk_size` is 0). `len` must be
+    /// a multiple of `chunk_size`. Only the checksums cross the network.
+    pub async fn checksum(
+        &self,
+        kind: ChecksumKind,
+        offset: u64,
+        len: usize,
+        chunk_size: usize,
+    ) -> Result<Vec<u64>, Error> {
+        let chunks = if chunk_size == 0 {
+            1
+        } else {
+            len.div_ceil(chunk_size)
+        };
+        let value_size = kind.value_size();
+        // le32 count followed by the values, each value_size bytes
+        let mut out = vec![0u8; 4 + chunks * value_size];
+        let init = kind.init_value();
+
+        let io_ctx = self.io_ctx()?;
+        let op = ReadOp::new()?;
+        unsafe {
+            rados_read_op_checksum(
+                op.ptr,
+                kind.as_raw(),
+                init.as_ptr() as *const c_char,
+                init.len(),
+                offset,
+                len,
+                chunk_size,
+                out.as_mut_ptr() as *mut c_char,
+                out.len(),
+                std::ptr::null_mut(),
+            )
+        };
+        op.operate(&io_ctx, self.name()).await?;
+
+        let count = u32::from_le_bytes(out[..4].try_into().unwrap()) as usize;
+        Ok(out[4..]
+            .chunks_exact(value_size)
+            .take(count)
+            .map(|value| match value_size {
+                4 => u32::from_le_bytes(value.try_into().unwrap()) as u64,
+                _ => u64::from_le_bytes(value.try_into().unwrap()),
+            })
+            .collect())
+    }
+
+    /// Call method `method` of object class `class` on the OSD with `input`,
+    /// returning whatever the method replies. Methods that modify the object
+    /// work too, but aren't tagged with the snap context, see `exec_write`.
+    pub async fn exec(&self, class: &str, method: &str, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let reply = self.exec_bytes(class, method, input).await?;
+        Ok(reply.to_vec())
+    }
+
+    /// Like `exec`, handing the reply back in the buffer librados allocated
+    /// for it instead of copying it.
+    pub async fn exec_bytes(
+        &self,
+        class: &str,
+        method: &str,
+        input: &[u8],
+    ) -> Result<Bytes, Error> {
+        let class = std::ffi::CString::new(class)?;
+        let method = std::ffi::CString::new(method)?;
+        let mut out: *mut c_char = std::ptr::null_mut();
+        let mut out_len: usize = 0;
+
+        let io_ctx = self.io_ctx()?;
+        let op = ReadOp::new()?;
+        unsafe {
+            rados_read_op_exec(
+                op.ptr,
+                class.as_ptr(),
+                method.as_ptr(),
+                input.as_ptr() as *const c_char,
+                input.len(),
+                &mut out,
+                &mut out_len,
+                std::ptr::null_mut(),
+            )
+        };
+        let result = op.operate(&io_ctx, self.name()).await;
+
+        // librados allocates the reply, whichever way the call went
+        let reply = unsafe { rados_buffer_to_bytes(out, out_len) };
+        result?;
+        Ok(reply)
+    }
+
+    /// Async reader over the object, see `AsyncObjectReader`.
+    #[cfg(any(feature = "tokio", feature = "futures-io"))]
+    pub fn reader(&self) -> Result<AsyncObjectReader<'a, A>, Error> {
+        AsyncObjectReader::new(self)
+    }
+
+    /// Check whether the object exists, without fetching its size or mtime.
+    pub async fn exists(&self) -> Result<bool, Error> {
+        let io_ctx = self.io_ctx()?;
+        let op = ReadOp::new()?;
+        unsafe { rados_read_op_assert_exists(op.ptr) };
+
+        match op.operate(&io_ctx, self.name()).await {
+            Ok(_) => Ok(true),
+            Err(e) if e == *ERROR_NOT_FOUND => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub(crate) async fn stat_if_exists(&self) -> Result<Option<Stat>, Error> {
+        match self.stat().await {
+            Ok(stat) => Ok(Some(stat)),
+            Err(e) if e == *ERROR_NOT_FOUND => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<'a> Object<'a> {
+    pub(crate) fn new(rados: &'a Rados, pool_name: &str, name: &str) -> Self {
+        Self::new_at(rados, pool_name, name, SnapId::HEAD)
+    }
+
+    /// Tag writes through this object with a self-managed snapshot context,
+    /// see `Pool::set_snap_context`.
+    pub fn set_snap_context(&mut self, ctx: Option<SnapContext>) {
+        self.snap_context = ctx;
+    }
+
+    pub fn snap_context(&self) -> Option<&SnapContext> {
+        self.snap_context.as_ref()
+    }
+
+    /// Async writer over the object, see `AsyncObjectWriter`.
+    #[cfg(any(feature = "tokio", feature = "futures-io"))]
+    pub fn writer(&self) -> Result<AsyncObjectWriter<'a>, Error> {
+        AsyncObjectWriter::new(self)
+    }
+
+    /// Create the object if it doesn't exist yet. Existing data is left alone,
+    /// unless `exclusive` is set, in which case `Error::AlreadyExists` is
+    /// returned.
+    pub async fn create(&self, exclusive: bool) -> Result<WriteResult, Error> {
+        let mode = if exclusive {
+            LIBRADOS_CREATE_EXCLUSIVE
+        } else {
+            LIBRADOS_CREATE_IDEMPOTENT
+        };
+        self.write_op(|op| unsafe { rados_write_op_create(op, mode as c_int, std::ptr::null()) })
+            .await
+            .map_err(|e| {
+                if e == *ERROR_EXISTS {
+                    Error::AlreadyExists(self.name.clone())
+                } else {
+                    e
+                }
+            })
+    }
+
+    /// Create the object together with `xattrs` in one op, failing with
+    /// `Error::AlreadyExists` if it exists.
+    pub(crate) async fn create_with_xattrs(
+        &self,
+        xattrs: &[(&str, &[u8])],
+    ) -> Result<WriteResult, Error> {
+        let names = xattrs
+            .iter()
+            .map(|(name, _)| std::ffi::CString::new(*name))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.write_op(|op| unsafe {
+            rados_write_op_create(op, LIBRADOS_CREATE_EXCLUSIVE as c_int, std::ptr::null());
+            for (name, (_, value)) in names.iter().zip(xattrs) {
+                rados_write_op_setxattr(
+                    op,
+                    name.as_ptr(),
+                    value.as_ptr() as *const c_char,
+                    value.len(),
+                );
+            }
+        })
+        .await
+        .map_err(|e| {
+            if e == *ERROR_EXISTS {
+                Error::AlreadyExists(self.name.clone())
+            } else {
+                e
+            }
+        })
+    }
+
+    /// Write `data` at `offset`, but only if the object currently holds
+    /// `expected` at that offset. On a mismatch nothing is written and
+    /// `Error::CompareMismatch` carries the object offset of the first byte
+    /// that differs.
+    pub async fn compare_and_write(
+        &self,
+        offset: u64,
+        expected: &[u8],
+        data: &[u8],
+    ) -> Result<WriteResult, Error> {
+        let result = self
+            .write_op(|op| unsafe {
+                rados_write_op_cmpext(
+                    op,
+                    expected.as_ptr() as *const c_char,
+                    expected.len(),
+                    offset,
+                    std::ptr::null_mut(),
+                );
+                rados_write_op_write(op, data.as_ptr() as *const c_char, data.len(), offset);
+            })
+            .await;
+
+        match result {
+            // cmpext reports a mismatch at byte n as -MAX_ERRNO - n
+            Err(Error::RadosError((code, _))) if code <= -MAX_ERRNO => {
+                Err(Error::CompareMismatch(offset + (-code - MAX_ERRNO) as u64))
+            }
+            result => result,
+        }
+    }
+
+    /// Set an xattr, but only if `expected <op> current value` holds, comparing
+    /// the values as strings; a missing xattr compares as empty. Fails with
+    /// `Error::XattrMismatch` otherwise, leaving the xattr untouched.
+    pub async fn set_xattr_if(
+        &self,
+        name: &str,
+        op: CmpXattrOp,
+        expected: &[u8],
+        value: &[u8],
+    ) -> Result<WriteResult, Error> {
+        let c_name = std::ffi::CString::new(name)?;
+        self.write_op(|wop| unsafe {
+            rados_write_op_cmpxattr(
+                wop,
+                c_name.as_ptr(),
+                op.as_raw(),
+                expected.as_ptr() as *const c_char,
+                expected.len(),
+            );
+            rados_write_op_setxattr(
+                wop,
+                c_name.as_ptr(),
+                value.as_ptr() as *const c_char,
+                value.len(),
+            );
+        })
+        .await
+        .map_err(|e| {
+            if e == *ERROR_CANCELED {
+                Error::XattrMismatch(name.to_string())
+            } else {
+                e
+            }
+        })
+    }
+
+    /// Call a modifying object class method as a write op, tagged with the
+    /// snap context. Write ops carry no reply data; use `exec` if the method
+    /// returns something.
+    pub async fn exec_write(
+        &self,
+        class: &str,
+        method: &str,
+        input: &[u8],
+    ) -> Result<WriteResult, Error> {
+        let class = std::ffi::CString::new(class)?;
+        let method = std::ffi::CString::new(method)?;
+        self.write_op(|op| unsafe {
+            rados_write_op_exec(
+                op,
+                class.as_ptr(),
+                method.as_ptr(),
+                input.as_ptr() as *const c_char,
+                input.len(),
+                std::ptr::null_mut(),
+            )
+        })
+        .await
+    }
+
+    // run a compound write op built by `add`, returning the version it produced
+    async fn write_op<F>(&self, add: F) -> Result<WriteResult, Error>
+    where
+        F: FnOnce(rados_write_op_t),
+    {
+        let io_ctx = self.io_ctx()?;
+        let op = WriteOp::new()?;
+        add(op.ptr);
+
+        let (_, version) = op.operate(&io_ctx, self.name()).await?;
+        Ok(WriteResult { version })
+    }
+
+    /// Write `buf` at `pos`. Like every write, returns a `WriteResult` with
+    /// the object version the write produced, see `if_version`.
+    pub async fn write(&self, pos: u64, buf: &[u8]) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
         let comp = AioCompletion::new()?;
         let key = std::ffi::CString::new(self.name())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
@@ -106,11 +907,11 @@ impl<'a> Object<'a> {
         assert!(code <= 0);
         check_error(code)?;
 
-        comp.await
+        complete(comp).await
     }
 
-    pub async fn write_full(&self, data: &[u8]) -> Result<usize, Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
+    pub async fn write_full(&self, data: &[u8]) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
         let comp = AioCompletion::new()?;
         let key = std::ffi::CString::new(self.name())?;
 
@@ -126,97 +927,213 @@ impl<'a> Object<'a> {
 
         assert!(code <= 0);
         check_error(code)?;
-        comp.await?;
-        Ok(data.len())
+        complete(comp).await
     }
 
-    pub async fn append(&self, data: &[u8]) -> Result<usize, Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
+    /// Like `write`, taking `data` by value. This is a convenience, not a
+    /// zero-copy write: librados copies the data into its own buffer when the
+    /// write is submitted, just as for `write`.
+    pub async fn write_bytes(&self, pos: u64, data: Bytes) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
         let comp = AioCompletion::new()?;
         let key = std::ffi::CString::new(self.name())?;
 
         let code = unsafe {
-            rados_aio_append(
+            rados_aio_write(
                 io_ctx.ptr,
                 key.as_ptr() as *mut c_char,
                 comp.ptr,
                 data.as_ptr() as *const c_char,
                 data.len(),
+                pos,
             )
         };
-
         assert!(code <= 0);
         check_error(code)?;
-        comp.await
+
+        complete_owned(BufferedCompletion::new(comp, data)).await
     }
 
-    pub async fn get_xattr(&self, name: &str) -> Result<Vec<u8>, Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
-        let key = std::ffi::CString::new(self.name())
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        let name = std::ffi::CString::new(name)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        let mut buf = Vec::with_capacity(64);
-
-        loop {
-            let comp = AioCompletion::new()?;
-            let code = unsafe {
-                rados_aio_getxattr(
-                    io_ctx.ptr,
-                    key.as_ptr() as *mut c_char,
-                    comp.ptr,
-                    name.as_ptr() as *const c_char,
-                    buf.as_mut_ptr() as *mut c_char,
-                    buf.capacity(),
-                )
-            };
-            assert!(code <= 0);
-            check_error(code)?;
-            match comp.await {
-                Ok(size) => {
-                    unsafe { buf.set_len(size) };
-                    break;
-                }
-                Err(e) => {
-                    if e == *ERROR_RANGE && buf.capacity() < MAX_BUF_SIZE {
-                        buf.reserve(buf.capacity() * 2);
-                    } else {
-                        return Err(e.into());
-                    }
-                }
+    /// Write several extents, given as `(offset, data)`, in one compound op.
+    /// The OSD applies the op atomically, so either all extents are written
+    /// or, on error, none are; unlike `read_vectored` there is no per-extent
+    /// result.
+    pub async fn write_vectored(&self, extents: &[(u64, &[u8])]) -> Result<WriteResult, Error> {
+        self.write_op(|op| unsafe {
+            for &(offset, data) in extents {
+                rados_write_op_write(op, data.as_ptr() as *const c_char, data.len(), offset);
             }
-        }
+        })
+        .await
+    }
 
-        Ok(buf)
+    /// `write_full` taking `data` by value, see `write_bytes`.
+    pub async fn write_full_bytes(&self, data: Bytes) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
+        let comp = AioCompletion::new()?;
+        let key = std::ffi::CString::new(self.name())?;
+
+        let code = unsafe {
+            rados_aio_write_full(
+                io_ctx.ptr,
+                key.as_ptr() as *mut c_char,
+                comp.ptr,
+                data.as_ptr() as *const c_char,
+                data.len(),
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+
+        complete_owned(BufferedCompletion::new(comp, data)).await
     }
 
-    pub async fn get_xattrs(&self) -> Result<Xattrs, Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
-        let key = std::ffi::CString::new(self.name())
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    /// Repeat `data` until `total_len` bytes starting at `offset` are written.
+    /// `total_len` must be a multiple of `data.len()`.
+    pub async fn write_same(
+        &self,
+        data: &[u8],
+        total_len: usize,
+        offset: u64,
+    ) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
+        let comp = AioCompletion::new()?;
+        let key = std::ffi::CString::new(self.name())?;
+
+        let code = unsafe {
+            rados_aio_writesame(
+                io_ctx.ptr,
+                key.as_ptr(),
+                comp.ptr,
+                data.as_ptr() as *const c_char,
+                data.len(),
+                total_len,
+                offset,
+            )
+        };
+
+        assert!(code <= 0);
+        check_error(code)?;
+        complete(comp).await
+    }
+
+    /// Zero `len` bytes starting at `offset`. The OSD may deallocate the range
+    /// instead of writing zeros.
+    pub async fn zero(&self, offset: u64, len: u64) -> Result<WriteResult, Error> {
+        self.write_op(|op| unsafe { rados_write_op_zero(op, offset, len) })
+            .await
+    }
+
+    /// Hint the OSD about the expected object and write sizes so it can
+    /// preallocate space. Creates the object if it doesn't exist.
+    pub async fn set_alloc_hint(
+        &self,
+        expected_size: u64,
+        expected_write_size: u64,
+        flags: AllocHintFlags,
+    ) -> Result<WriteResult, Error> {
+        self.write_op(|op| unsafe {
+            rados_write_op_set_alloc_hint2(op, expected_size, expected_write_size, flags.bits())
+        })
+        .await
+    }
+
+    /// Append `data` to the object.
+    ///
+    /// Pools that require aligned appends (erasure coded pools without
+    /// overwrites, see `Pool::requires_alignment`) only take multiples of the
+    /// alignment here, anything else fails with `Error::Unaligned` before it
+    /// is sent. Use `appender` to append arbitrary chunks to those.
+    pub async fn append(&self, data: &[u8]) -> Result<WriteResult, Error> {
+        self.check_aligned(data.len())?;
+        self.append_unaligned(data).await
+    }
+
+    // fail appends of `len` bytes the pool would reject, or would take only
+    // as the last one
+    fn check_aligned(&self, len: usize) -> Result<(), Error> {
+        match self.alignment()? {
+            0 => Ok(()),
+            alignment if (len as u64).is_multiple_of(alignment) => Ok(()),
+            alignment => Err(Error::Unaligned(alignment)),
+        }
+    }
 
+    // `append` without the alignment check, for the tail `Appender` ends with
+    async fn append_unaligned(&self, data: &[u8]) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
         let comp = AioCompletion::new()?;
-        let mut iter = RadosXattrsIter::new(std::ptr::null_mut());
+        let key = std::ffi::CString::new(self.name())?;
 
         let code = unsafe {
-            rados_aio_getxattrs(
+            rados_aio_append(
                 io_ctx.ptr,
                 key.as_ptr() as *mut c_char,
                 comp.ptr,
-                &mut iter.ptr,
+                data.as_ptr() as *const c_char,
+                data.len(),
             )
         };
+
         assert!(code <= 0);
         check_error(code)?;
-        comp.await?;
+        complete(comp).await
+    }
 
-        let xattrs = Xattrs::from_iter(&iter)?;
+    /// `append` taking `data` by value, see `write_bytes`.
+    pub async fn append_bytes(&self, data: Bytes) -> Result<WriteResult, Error> {
+        self.check_aligned(data.len())?;
+        let io_ctx = self.io_ctx()?;
+        let comp = AioCompletion::new()?;
+        let key = std::ffi::CString::new(self.name())?;
+        let code = unsafe {
+            rados_aio_append(
+                io_ctx.ptr,
+                key.as_ptr() as *mut c_char,
+                comp.ptr,
+                data.as_ptr() as *const c_char,
+                data.len(),
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
 
-        Ok(xattrs)
+        complete_owned(BufferedCompletion::new(comp, data)).await
     }
 
-    pub async fn set_xattr(&self, name: &str, value: &[u8]) -> Result<(), Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
+    /// Append to the object in chunks of any size, also on pools that
+    /// require aligned appends. There only whole multiples of the alignment
+    /// are written and the unaligned tail is held back until the next chunk;
+    /// `Appender::finish` or `Appender::finish_padded` writes it out.
+    pub fn appender(&self) -> Result<Appender<'_, 'a>, Error> {
+        Ok(Appender {
+            object: self,
+            alignment: self.alignment()? as usize,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Alignment the pool requires for writes, 0 if it accepts any.
+    pub(crate) fn alignment(&self) -> Result<u64, Error> {
+        if let Some(alignment) = self.alignment.get() {
+            return Ok(*alignment);
+        }
+
+        let io_ctx = self.io_ctx()?;
+        let mut requires: c_int = 0;
+        let code = unsafe { rados_ioctx_pool_requires_alignment2(io_ctx.ptr, &mut requires) };
+        check_error(code)?;
+        let mut alignment: u64 = 0;
+        if requires != 0 {
+            let code = unsafe { rados_ioctx_pool_required_alignment2(io_ctx.ptr, &mut alignment) };
+            check_error(code)?;
+        }
+
+        Ok(*self.alignment.get_or_init(|| alignment))
+    }
+
+    pub async fn set_xattr(&self, name: &str, value: &[u8]) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
         let key = std::ffi::CString::new(self.name())
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
         let name = std::ffi::CString::new(name)
@@ -235,29 +1152,667 @@ impl<'a> Object<'a> {
         };
         assert!(code <= 0);
         check_error(code)?;
-        match comp.await {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e.into()),
+        complete(comp).await
+    }
+
+    pub async fn remove_xattr(&self, name: &str) -> Result<WriteResult, Error> {
+        let io_ctx = self.io_ctx()?;
+        let key = std::ffi::CString::new(self.name())?;
+        let name = std::ffi::CString::new(name)?;
+        let comp = AioCompletion::new()?;
+
+        let code = unsafe {
+            rados_aio_rmxattr(
+                io_ctx.ptr,
+                key.as_ptr() as *mut c_char,
+                comp.ptr,
+                name.as_ptr() as *const c_char,
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        complete(comp).await
+    }
+
+    pub async fn truncate(&self, size: u64) -> Result<WriteResult, Error> {
+        self.write_op(|op| unsafe { rados_write_op_truncate(op, size) })
+            .await
+    }
+
+    pub(crate) async fn remove(&self) -> Result<(), Error> {
+        self.write_op(|op| unsafe { rados_write_op_remove(op) })
+            .await?;
+        Ok(())
+    }
+
+    /// Make the writes issued through the returned handle conditional on the
+    /// object being at `version`, as returned by `read_versioned` or a
+    /// previous write. A write that finds another version fails with
+    /// `Error::VersionMismatch` and changes nothing.
+    pub fn if_version(&self, version: u64) -> VersionedWrite<'_, 'a> {
+        VersionedWrite {
+            object: self,
+            version,
         }
     }
+}
 
-    pub fn truncate(&self, size: u64) -> Result<(), Error> {
-        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
-        let key = std::ffi::CString::new(self.name())
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+/// Writes to an object guarded by a version check, see `Object::if_version`.
+#[derive(Debug)]
+pub struct VersionedWrite<'o, 'a> {
+    object: &'o Object<'a>,
+    version: u64,
+}
 
-        let code = unsafe { rados_trunc(io_ctx.ptr, key.as_ptr() as *mut c_char, size) };
+impl VersionedWrite<'_, '_> {
+    pub async fn write(&self, pos: u64, buf: &[u8]) -> Result<WriteResult, Error> {
+        self.operate(|op| unsafe {
+            rados_write_op_write(op, buf.as_ptr() as *const c_char, buf.len(), pos)
+        })
+        .await
+    }
 
-        assert!(code <= 0);
-        check_error(code)
+    pub async fn write_full(&self, data: &[u8]) -> Result<WriteResult, Error> {
+        self.operate(|op| unsafe {
+            rados_write_op_write_full(op, data.as_ptr() as *const c_char, data.len())
+        })
+        .await
+    }
+
+    pub async fn append(&self, data: &[u8]) -> Result<WriteResult, Error> {
+        self.object.check_aligned(data.len())?;
+        self.operate(|op| unsafe {
+            rados_write_op_append(op, data.as_ptr() as *const c_char, data.len())
+        })
+        .await
+    }
+
+    pub async fn truncate(&self, size: u64) -> Result<WriteResult, Error> {
+        self.operate(|op| unsafe { rados_write_op_truncate(op, size) })
+            .await
+    }
+
+    pub async fn set_xattr(&self, name: &str, value: &[u8]) -> Result<WriteResult, Error> {
+        let name = std::ffi::CString::new(name)?;
+        self.operate(|op| unsafe {
+            rados_write_op_setxattr(
+                op,
+                name.as_ptr(),
+                value.as_ptr() as *const c_char,
+                value.len(),
+            )
+        })
+        .await
+    }
+
+    pub async fn remove_xattr(&self, name: &str) -> Result<WriteResult, Error> {
+        let name = std::ffi::CString::new(name)?;
+        self.operate(|op| unsafe { rados_write_op_rmxattr(op, name.as_ptr()) })
+            .await
+    }
+
+    pub async fn remove(&self) -> Result<(), Error> {
+        self.operate(|op| unsafe { rados_write_op_remove(op) })
+            .await?;
+        Ok(())
+    }
+
+    async fn operate<F>(&self, add: F) -> Result<WriteResult, Error>
+    where
+        F: FnOnce(rados_write_op_t),
+    {
+        let version = self.version;
+        self.object
+            .write_op(|op| {
+                unsafe { rados_write_op_assert_version(op, version) };
+                add(op);
+            })
+            .await
+            .map_err(|e| {
+                // ERANGE or EOVERFLOW depending on which side is newer
+                if e == *ERROR_RANGE || e == *ERROR_OVERFLOW {
+                    Error::VersionMismatch(version)
+                } else {
+                    e
+                }
+            })
     }
 }
 
+/// Aligned appends to an object, see `Object::appender`.
+///
+/// The tail held back for alignment is only written by `finish` or
+/// `finish_padded`; dropping the appender discards it.
 #[derive(Debug)]
+#[must_use = "the unaligned tail is only written by `finish` or `finish_padded`"]
+pub struct Appender<'o, 'a> {
+    object: &'o Object<'a>,
+    // 0 when the pool accepts unaligned appends
+    alignment: usize,
+    pending: Vec<u8>,
+}
+
+impl Appender<'_, '_> {
+    /// Append `data`, writing out everything up to the last alignment
+    /// boundary. On error nothing of `data` is written or held back.
+    pub async fn append(&mut self, data: &[u8]) -> Result<usize, Error> {
+        if self.alignment == 0 {
+            self.object.append_unaligned(data).await?;
+            return Ok(data.len());
+        }
+
+        self.pending.extend_from_slice(data);
+        let aligned = self.pending.len() - self.pending.len() % self.alignment;
+        if aligned > 0 {
+            if let Err(e) = self.object.append_unaligned(&self.pending[..aligned]).await {
+                self.pending.truncate(self.pending.len() - data.len());
+                return Err(e);
+            }
+            self.pending.drain(..aligned);
+        }
+
+        Ok(data.len())
+    }
+
+    /// Bytes held back until the next alignment boundary.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Write out the held back tail as is. Erasure coded pools accept an
+    /// unaligned append only as the last one, so further appends to the
+    /// object will fail. The tail is lost if the append fails.
+    pub async fn finish(self) -> Result<(), Error> {
+        self.finish_pending(false).await
+    }
+
+    /// Write out the held back tail padded with zeros up to the pool
+    /// alignment, so the object can still be appended to. The tail is lost
+    /// if the append fails.
+    pub async fn finish_padded(self) -> Result<(), Error> {
+        self.finish_pending(true).await
+    }
+
+    async fn finish_pending(mut self, pad: bool) -> Result<(), Error> {
+        let mut buf = std::mem::take(&mut self.pending);
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        if pad {
+            buf.resize(buf.len().div_ceil(self.alignment) * self.alignment, 0);
+        }
+        self.object.append_unaligned(&buf).await?;
+        Ok(())
+    }
+}
+
+/// Checksum algorithms supported by `Object::checksum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumKind {
+    XxHash32,
+    XxHash64,
+    Crc32c,
+}
+
+impl ChecksumKind {
+    fn as_raw(self) -> rados_checksum_type_t {
+        match self {
+            ChecksumKind::XxHash32 => rados_checksum_type_t_LIBRADOS_CHECKSUM_TYPE_XXHASH32,
+            ChecksumKind::XxHash64 => rados_checksum_type_t_LIBRADOS_CHECKSUM_TYPE_XXHASH64,
+            ChecksumKind::Crc32c => rados_checksum_type_t_LIBRADOS_CHECKSUM_TYPE_CRC32C,
+        }
+    }
+
+    fn value_size(self) -> usize {
+        match self {
+            ChecksumKind::XxHash64 => 8,
+            _ => 4,
+        }
+    }
+
+    // seed for the xxhash variants, initial crc for crc32c as used by ceph
+    fn init_value(self) -> Vec<u8> {
+        match self {
+            ChecksumKind::XxHash32 => 0u32.to_le_bytes().to_vec(),
+            ChecksumKind::XxHash64 => 0u64.to_le_bytes().to_vec(),
+            ChecksumKind::Crc32c => u32::MAX.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+bitflags! {
+    /// Access pattern hints for `Object::set_alloc_hint`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct AllocHintFlags: u32 {
+        const SEQUENTIAL_WRITE = LIBRADOS_ALLOC_HINT_FLAG_SEQUENTIAL_WRITE;
+        const RANDOM_WRITE = LIBRADOS_ALLOC_HINT_FLAG_RANDOM_WRITE;
+        const SEQUENTIAL_READ = LIBRADOS_ALLOC_HINT_FLAG_SEQUENTIAL_READ;
+        const RANDOM_READ = LIBRADOS_ALLOC_HINT_FLAG_RANDOM_READ;
+        const APPEND_ONLY = LIBRADOS_ALLOC_HINT_FLAG_APPEND_ONLY;
+        const IMMUTABLE = LIBRADOS_ALLOC_HINT_FLAG_IMMUTABLE;
+        const SHORTLIVED = LIBRADOS_ALLOC_HINT_FLAG_SHORTLIVED;
+        const LONGLIVED = LIBRADOS_ALLOC_HINT_FLAG_LONGLIVED;
+        const COMPRESSIBLE = LIBRADOS_ALLOC_HINT_FLAG_COMPRESSIBLE;
+        const INCOMPRESSIBLE = LIBRADOS_ALLOC_HINT_FLAG_INCOMPRESSIBLE;
+    }
+}
+
+/// Result of a write to an object.
+///
+/// Writes used to return the bare object version, which read like a byte
+/// count; it is wrapped so code written against that fails to compile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WriteResult {
+    /// Object version the write produced, see `Object::if_version`.
+    pub version: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Stat {
     pub size: u64,
     pub mtime: (u64, u64),
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::pool::Pool;
+    use crate::rados::Rados;
+
+    #[tokio::test]
+    async fn test_object_version() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        let obj = pool.object("versioned");
+        let v1 = obj.write_full(b"v1").await.unwrap();
+
+        // a second writer updates the object behind our back
+        let other = pool.object("versioned");
+        let v1 = v1.version;
+        let v2 = other
+            .if_version(v1)
+            .write_full(b"v2")
+            .await
+            .unwrap()
+            .version;
+        assert!(v2 > v1);
+
+        match obj.if_version(v1).write_full(b"v3").await {
+            Err(Error::VersionMismatch(v)) => assert_eq!(v, v1),
+            other => panic!("expected VersionMismatch, got {:?}", other),
+        }
+
+        // concurrent reads each get the version of their own data
+        let (a, b) = futures::join!(obj.read_versioned(..), other.read_versioned(..));
+        assert_eq!(a.unwrap(), (Bytes::from_static(b"v2"), v2));
+        assert_eq!(b.unwrap(), (Bytes::from_static(b"v2"), v2));
+
+        // every kind of read reports the version
+        let mut buf = [0u8; 8];
+        assert_eq!(obj.read_with_version(0, &mut buf).await.unwrap(), (2, v2));
+        assert_eq!(obj.read_with_version(0, &mut []).await.unwrap(), (0, v2));
+        assert_eq!(obj.stat_with_version().await.unwrap().1, v2);
+        assert_eq!(
+            obj.read_bytes_with_version(0, 8).await.unwrap(),
+            (Bytes::from_static(b"v2"), v2)
+        );
+        let (extents, version) = obj
+            .read_vectored_with_version(&[(0, 1), (1, 1)])
+            .await
+            .unwrap();
+        assert_eq!(extents.len(), 2);
+        assert_eq!(version, v2);
+
+        // reads of what a write produced see its version
+        let v3 = obj.set_xattr("key", b"value").await.unwrap().version;
+        assert!(v3 > v2);
+        assert_eq!(
+            obj.get_xattr_with_version("key").await.unwrap(),
+            (b"value".to_vec(), v3)
+        );
+        assert_eq!(obj.get_xattrs_with_version().await.unwrap().1, v3);
+        obj.if_version(v3).write_full(b"v3").await.unwrap();
+
+        pool.remove_object("versioned").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_compare_and_write() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        let obj = pool.put_object("cmpext", b"0123456789").await.unwrap();
+        obj.compare_and_write(2, b"234", b"abc").await.unwrap();
+
+        match obj.compare_and_write(2, b"abX", b"xyz").await {
+            Err(Error::CompareMismatch(offset)) => assert_eq!(offset, 4),
+            other => panic!("expected CompareMismatch, got {:?}", other),
+        }
+
+        let mut buf = [0u8; 10];
+        obj.read(0, &mut buf).await.unwrap();
+        assert_eq!(&buf, b"01abc56789");
+
+        obj.set_xattr_if("lease", CmpXattrOp::Eq, b"", b"owner1")
+            .await
+            .unwrap();
+        match obj
+            .set_xattr_if("lease", CmpXattrOp::Eq, b"", b"owner2")
+            .await
+        {
+            Err(Error::XattrMismatch(name)) => assert_eq!(name, "lease"),
+            other => panic!("expected XattrMismatch, got {:?}", other),
+        }
+        assert_eq!(obj.get_xattr_str("lease").await.unwrap(), "owner1");
+
+        pool.remove_object("cmpext").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_zero_write_same() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let _ = pool.remove_object("preallocated").await;
+
+        let obj = pool.object("preallocated");
+        obj.set_alloc_hint(
+            64 * 1024,
+            4096,
+            AllocHintFlags::SEQUENTIAL_WRITE | AllocHintFlags::LONGLIVED,
+        )
+        .await
+        .unwrap();
+        assert!(obj.exists().await.unwrap());
+
+        obj.write_same(b"ab", 8, 0).await.unwrap();
+        obj.zero(2, 4).await.unwrap();
+
+        let mut buf = [0xffu8; 8];
+        assert_eq!(obj.read(0, &mut buf).await.unwrap(), 8);
+        assert_eq!(&buf, b"ab\0\0\0\0ab");
+
+        pool.remove_object("preallocated").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_checksum() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        let obj = pool.put_object("checksum", &[7u8; 8192]).await.unwrap();
+        for kind in [
+            ChecksumKind::XxHash32,
+            ChecksumKind::XxHash64,
+            ChecksumKind::Crc32c,
+        ] {
+            let sums = obj.checksum(kind, 0, 8192, 4096).await.unwrap();
+            assert_eq!(sums.len(), 2);
+            // identical chunks hash the same
+            assert_eq!(sums[0], sums[1]);
+            assert_eq!(obj.checksum(kind, 0, 8192, 0).await.unwrap().len(), 1);
+        }
+
+        obj.write(4096, &[8u8; 16]).await.unwrap();
+        let sums = obj
+            .checksum(ChecksumKind::Crc32c, 0, 8192, 4096)
+            .await
+            .unwrap();
+        assert_ne!(sums[0], sums[1]);
+
+        pool.remove_object("checksum").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_bytes() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool.object("bytes");
+
+        obj.write_full_bytes(Bytes::from_static(b"hello"))
+            .await
+            .unwrap();
+        obj.append_bytes(Bytes::from_static(b" world"))
+            .await
+            .unwrap();
+        obj.write_bytes(0, Bytes::from_static(b"J")).await.unwrap();
+        assert_eq!(obj.read_bytes(0, 64).await.unwrap(), "Jello world");
+
+        // read_buf appends after what the buffer already holds
+        let mut buf = BytesMut::with_capacity(8);
+        buf.extend_from_slice(b">");
+        let buf = obj.read_buf(6, buf).await.unwrap();
+        assert_eq!(&buf[..], b">world");
+
+        obj.set_xattr("a", b"1").await.unwrap();
+        obj.set_xattr("b", b"").await.unwrap();
+        let xattrs = obj.get_xattrs().await.unwrap();
+        assert_eq!(xattrs.get("a"), Some(&b"1"[..]));
+        assert_eq!(xattrs.get("b"), Some(&b""[..]));
+
+        pool.remove_object("bytes").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_appender() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool.put_object("appender", b"").await.unwrap();
+
+        let mut appender = obj.appender().unwrap();
+        let mut expected = Vec::new();
+        for i in 0..10u8 {
+            let chunk = vec![i; 1000 + i as usize];
+            appender.append(&chunk).await.unwrap();
+            expected.extend_from_slice(&chunk);
+        }
+        appender.finish().await.unwrap();
+
+        assert_eq!(obj.read_to_end().await.unwrap(), expected);
+
+        // the test pool takes any append, so pretend it requires alignment
+        let obj = pool.put_object("appender", b"").await.unwrap();
+        obj.alignment.set(64).unwrap();
+        assert_eq!(
+            obj.append(b"short").await.unwrap_err(),
+            Error::Unaligned(64)
+        );
+        obj.append(&[1; 128]).await.unwrap();
+
+        let mut appender = obj.appender().unwrap();
+        appender.append(&[2; 100]).await.unwrap();
+        assert_eq!(appender.pending(), 36);
+        assert_eq!(obj.stat().await.unwrap().size, 192);
+        appender.append(&[3; 30]).await.unwrap();
+        assert_eq!(appender.pending(), 2);
+        assert_eq!(obj.stat().await.unwrap().size, 256);
+        appender.finish_padded().await.unwrap();
+
+        let mut expected = vec![1; 128];
+        expected.extend_from_slice(&[2; 100]);
+        expected.extend_from_slice(&[3; 30]);
+        expected.resize(320, 0);
+        assert_eq!(obj.read_to_end().await.unwrap(), expected);
+
+        // a tail that is not padded ends the object for aligned appends
+        let mut appender = obj.appender().unwrap();
+        appender.append(b"end").await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, 320);
+        appender.finish().await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, 323);
+
+        pool.remove_object("appender").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_vectored() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool.put_object("vectored", &[b'.'; 64]).await.unwrap();
+
+        obj.write_vectored(&[(0, b"head"), (30, b"middle"), (60, b"tail")])
+            .await
+            .unwrap();
+
+        let extents = obj
+            .read_vectored(&[(0, 4), (28, 10), (62, 16), (100, 8), (10, 0)])
+            .await
+            .unwrap();
+        let extents: Vec<Bytes> = extents.into_iter().map(Result::unwrap).collect();
+        assert_eq!(extents[0], "head");
+        assert_eq!(extents[1], "..middle..");
+        // cut short at the end of the object
+        assert_eq!(extents[2], "il");
+        assert!(extents[3].is_empty());
+        assert!(extents[4].is_empty());
+
+        let missing = pool.object("vectored-missing");
+        assert_eq!(
+            missing.read_vectored(&[(0, 4)]).await.unwrap_err(),
+            *ERROR_NOT_FOUND
+        );
+
+        pool.remove_object("vectored").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_read_range() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        // larger than the first read so read_to_end needs follow-up reads
+        let data: Vec<u8> = (0..IO_BUF_SIZE as u32 * 2 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let obj = pool.put_object("read-range", &data).await.unwrap();
+
+        assert_eq!(obj.read_to_end().await.unwrap(), data);
+        assert_eq!(obj.read_range(10..20).await.unwrap(), &data[10..20]);
+        assert_eq!(obj.read_range(10..=20).await.unwrap(), &data[10..=20]);
+        let tail = data.len() as u64 - 5;
+        assert_eq!(
+            obj.read_range(tail..).await.unwrap(),
+            &data[data.len() - 5..]
+        );
+        // clamped to the object size
+        assert_eq!(obj.read_range(tail..tail + 100).await.unwrap().len(), 5);
+        assert!(obj.read_range(tail + 100..).await.unwrap().is_empty());
+        // bounds at the end of the offset space
+        assert_eq!(obj.read_range(..=u64::MAX).await.unwrap(), data);
+        assert_eq!(obj.read_range(10..u64::MAX).await.unwrap(), &data[10..]);
+        assert!(obj.read_range(u64::MAX..).await.unwrap().is_empty());
+        assert!(obj
+            .read_range((Bound::Included(20), Bound::Excluded(10)))
+            .await
+            .unwrap()
+            .is_empty());
+
+        pool.remove_object("read-range").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_xattrs() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        let obj = pool.put_object("xattrs", b"test1").await.unwrap();
+        obj.set_xattr("str", b"value").await.unwrap();
+        obj.set_xattr("json", b"[1, 2, 3]").await.unwrap();
+
+        assert_eq!(obj.get_xattr_str("str").await.unwrap(), "value");
+        assert_eq!(
+            obj.get_xattr_as::<Vec<u32>>("json").await.unwrap(),
+            vec![1, 2, 3]
+        );
+
+        let xattrs = obj.get_xattrs().await.unwrap();
+        assert_eq!(xattrs.len(), 2);
+        assert!(xattrs.contains_key("json"));
+        assert_eq!(xattrs.get("str"), Some(&b"value"[..]));
+
+        obj.remove_xattr("str").await.unwrap();
+        obj.remove_xattr("json").await.unwrap();
+        assert!(obj.get_xattrs().await.unwrap().is_empty());
+        assert!(obj.get_xattr("str").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_object_xattr_sizes() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool.put_object("xattr-sizes", b"test1").await.unwrap();
+
+        let sizes = [
+            0,
+            63,
+            64,
+            65,
+            XATTR_INITIAL_SIZE - 1,
+            XATTR_INITIAL_SIZE,
+            XATTR_INITIAL_SIZE + 1,
+            5 * 1024 * 1024,
+        ];
+        for size in sizes {
+            let value: Vec<u8> = (0..size).map(|i| i as u8).collect();
+            obj.set_xattr("sized", &value).await.unwrap();
+            assert_eq!(obj.get_xattr("sized").await.unwrap(), value);
+        }
+
+        obj.set_xattr("empty", b"").await.unwrap();
+        let xattrs = obj.get_xattrs().await.unwrap();
+        assert_eq!(xattrs.get("empty"), Some(&b""[..]));
+        assert!(xattrs.contains_key("sized"));
+
+        let mut obj = obj;
+        obj.set_max_xattr_size(1024);
+        assert_eq!(
+            obj.get_xattr("sized").await.unwrap_err(),
+            Error::XattrTooLarge("sized".to_string())
+        );
+        obj.set_max_xattr_size(6 * 1024 * 1024);
+        assert_eq!(obj.get_xattr("sized").await.unwrap().len(), 5 * 1024 * 1024);
+        obj.set_max_xattr_size(64);
+        assert_eq!(obj.get_xattr("empty").await.unwrap(), b"");
+        assert_eq!(
+            obj.get_xattr("sized").await.unwrap_err(),
+            Error::XattrTooLarge("sized".to_string())
+        );
+
+        obj.remove_xattr("sized").await.unwrap();
+        obj.remove_xattr("empty").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_object_snap_versions() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        for snap in ["snap1", "snap2", "snap3"] {
+            if pool.snapshot_lookup(snap).await.is_ok() {
+                pool.snapshot_remove(snap).await.unwrap();
+            }
+        }
+
+        let obj = pool.put_object("snap-versions", b"test1").await.unwrap();
+        pool.snapshot_create("snap1").await.unwrap();
+        pool.snapshot_create("snap2").await.unwrap();
+        obj.write_full(b"test22").await.unwrap();
+        pool.snapshot_create("snap3").await.unwrap();
+
+        let versions = obj.snap_versions().await.unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].size, 5);
+        assert_eq!(
+            versions[0].snaps,
+            vec![
+                pool.snapshot_lookup("snap1").await.unwrap(),
+                pool.snapshot_lookup("snap2").await.unwrap()
+            ]
+        );
+
+        for snap in ["snap1", "snap2", "snap3"] {
+            pool.snapshot_remove(snap).await.unwrap();
+        }
+    }
+}
@@ -1,18 +1,33 @@
+use crate::buffer::MAX_BUF_SIZE;
 use crate::errors::Error::OtherError;
 use crate::errors::{check_error, Error, ERROR_RANGE};
-use crate::io::{AioCompletion, IoCtx};
-use crate::object::Object;
+use crate::io::{AioCompletion, BufferedCompletion, IoCtx, WriteOp};
+use crate::object::{ChecksumKind, Object, ReadOnly, ReadWrite};
 use crate::rados::Rados;
-use crate::utils::c_char_ptr_to_string;
+use crate::snapshot::{ObjectChange, ObjectDiff, SnapContext, SnapId, Snapshot};
+use crate::striper::NativeStripedObject;
+#[cfg(feature = "striper")]
+use crate::striper::StripedObject;
+use crate::utils::{blocking, blocking_in_place, c_char_ptr_to_string, split_nul_terminated};
 use anyhow::anyhow;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
 use librados_sys::*;
+use std::collections::{BTreeSet, HashMap};
 use std::ffi::CString;
-use std::os::raw::c_char;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_int};
 use std::ptr::{null, null_mut};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+#[derive(Debug)]
 pub struct Pool<'a> {
     name: String,
     rados: &'a Rados,
+    snap_context: Option<SnapContext>,
+    // empty for the default namespace
+    namespace: String,
 }
 
 impl<'a> Pool<'a> {
@@ -20,6 +35,8 @@ impl<'a> Pool<'a> {
         Pool {
             name: name.to_string(),
             rados,
+            snap_context: None,
+            namespace: String::new(),
         }
     }
 
@@ -27,24 +44,91 @@ impl<'a> Pool<'a> {
         &self.name
     }
 
-    pub async fn get_object(&self, name: &str) -> Result<Object, Error> {
-        let obj = Object::new(self.rados, &self.name, name);
-        obj.stat().await?;
+    /// Tag writes with a self-managed snapshot context. Applies to objects
+    /// obtained from the pool after the call.
+    pub fn set_snap_context(&mut self, ctx: Option<SnapContext>) {
+        self.snap_context = ctx;
+    }
+
+    pub fn snap_context(&self) -> Option<&SnapContext> {
+        self.snap_context.as_ref()
+    }
+
+    /// Work on the objects in `namespace`, the default one if empty. Applies
+    /// to objects, listings and snapshot views obtained from the pool after
+    /// the call. Striped objects always live in the default namespace.
+    pub fn set_namespace(&mut self, namespace: &str) {
+        self.namespace = namespace.to_string();
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Handle to an object in this pool. No I/O is done, so the object need
+    /// not exist; see `get_object` for a checked lookup.
+    pub fn object(&self, name: &str) -> Object<'a> {
+        Object::new(self.rados, &self.name, name)
+            .with_namespace(&self.namespace)
+            .with_snap_context(self.snap_context.clone())
+    }
+
+    /// Handle to a striped object in this pool, see `striper`.
+    pub fn native_striped_object(&self, name: &str) -> NativeStripedObject<'a> {
+        NativeStripedObject::new(self.rados, &self.name, name, self.snap_context.clone())
+    }
+
+    /// Handle to a striped object in this pool, accessed through
+    /// libradosstriper.
+    #[cfg(feature = "striper")]
+    pub fn striped_object(&self, name: &str) -> StripedObject<'a> {
+        StripedObject::new(self.rados, &self.name, name, self.snap_context.clone())
+    }
+
+    // io context for writes issued by the pool itself, tagged with the snap
+    // context and addressing the pool namespace
+    fn io_ctx(&self) -> Result<IoCtx<'a>, Error> {
+        let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
+        if !self.namespace.is_empty() {
+            io_ctx.set_namespace(&self.namespace)?;
+        }
+        if let Some(ctx) = &self.snap_context {
+            io_ctx.set_write_ctx(ctx)?;
+        }
+        Ok(io_ctx)
+    }
+
+    /// Handle to an existing object, failing with `ENOENT` if there is none.
+    pub async fn get_object(&self, name: &str) -> Result<Object<'a>, Error> {
+        let obj = self.object(name);
+        if !obj.exists().await? {
+            return Err(check_error(-2).unwrap_err());
+        }
         Ok(obj)
     }
 
-    pub async fn put_object(&self, name: &str, data: &[u8]) -> Result<Object, Error> {
-        let obj = Object::new(self.rados, &self.name, name);
+    pub async fn put_object(&self, name: &str, data: &[u8]) -> Result<Object<'a>, Error> {
+        let obj = self.object(name);
         obj.write_full(data).await?;
         Ok(obj)
     }
 
-    pub async fn create_object(&self, name: &str) -> Result<Object, Error> {
-        self.put_object(name, &[]).await
+    /// Create an empty object, leaving an existing one untouched.
+    pub async fn create_object(&self, name: &str) -> Result<Object<'a>, Error> {
+        let obj = self.object(name);
+        obj.create(false).await?;
+        Ok(obj)
+    }
+
+    /// Create an empty object, failing with `Error::AlreadyExists` if it exists.
+    pub async fn create_object_exclusive(&self, name: &str) -> Result<Object<'a>, Error> {
+        let obj = self.object(name);
+        obj.create(true).await?;
+        Ok(obj)
     }
 
     pub async fn remove_object(&self, name: &str) -> Result<(), Error> {
-        let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
+        let io_ctx = self.io_ctx()?;
         let comp = AioCompletion::new()?;
         let key = std::ffi::CString::new(name)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
@@ -57,180 +141,652 @@ impl<'a> Pool<'a> {
         Ok(())
     }
 
+    /// Iterate over the objects in the pool namespace. `next` blocks while it
+    /// fetches the next batch of names, see `list_object_names` for async
+    /// code.
     pub fn list_objects(&self) -> Result<ListObjectResultIter, Error> {
+        let mut iter =
+            ListObjectResultIter::open(self.rados, &self.name, &self.namespace, SnapId::HEAD)?;
+        iter.snap_context = self.snap_context.clone();
+        Ok(iter)
+    }
+
+    /// Names of the objects in the pool namespace, listed off the async
+    /// runtime, see "Blocking calls" in the README.
+    pub async fn list_object_names(&self) -> Result<Vec<String>, Error> {
+        list_names(self.rados, &self.name, &self.namespace, SnapId::HEAD).await
+    }
+
+    /// A read-only view of the pool as of snapshot `snap_id`.
+    pub fn at_snapshot(&self, snap_id: SnapId) -> PoolSnapshot<'a> {
+        PoolSnapshot {
+            name: self.name.clone(),
+            namespace: self.namespace.clone(),
+            rados: self.rados,
+            snap_id,
+        }
+    }
+
+    pub async fn stat(&self) -> Result<Stat, Error> {
+        let (rados, pool_name) = (self.rados.detach(), self.name.clone());
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            let mut stat = rados_pool_stat_t {
+                num_bytes: 0,
+                num_kb: 0,
+                num_objects: 0,
+                num_object_clones: 0,
+                num_object_copies: 0,
+                num_objects_missing_on_primary: 0,
+                num_objects_unfound: 0,
+                num_objects_degraded: 0,
+                num_rd: 0,
+                num_rd_kb: 0,
+                num_wr: 0,
+                num_wr_kb: 0,
+                num_user_bytes: 0,
+                compressed_bytes_orig: 0,
+                compressed_bytes: 0,
+                compressed_bytes_alloc: 0,
+            };
+            let code = unsafe { rados_ioctx_pool_stat(io_ctx.ptr, &mut stat) };
+            check_error(code)?;
+
+            Ok(Stat {
+                num_bytes: stat.num_bytes,
+                num_kb: stat.num_kb,
+                num_objects: stat.num_objects,
+                num_object_clones: stat.num_object_clones,
+                num_object_copies: stat.num_object_copies,
+                num_objects_missing_on_primary: stat.num_objects_missing_on_primary,
+                num_objects_unfound: stat.num_objects_unfound,
+                num_objects_degraded: stat.num_objects_degraded,
+                num_rd: stat.num_rd,
+                num_rd_kb: stat.num_rd_kb,
+                num_wr: stat.num_wr,
+                num_wr_kb: stat.num_wr_kb,
+                num_user_bytes: stat.num_user_bytes,
+                compressed_bytes_orig: stat.compressed_bytes_orig,
+                compressed_bytes: stat.compressed_bytes,
+                compressed_bytes_alloc: stat.compressed_bytes_alloc,
+            })
+        })
+        .await
+    }
+
+    /// Whether writes to the pool must be aligned, which is the case for
+    /// erasure coded pools without overwrite support.
+    pub fn requires_alignment(&self) -> Result<bool, Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-        let mut list_ctx = null_mut();
+        let mut requires: c_int = 0;
 
-        let code = unsafe { rados_nobjects_list_open(io_ctx.ptr, &mut list_ctx) };
+        let code = unsafe { rados_ioctx_pool_requires_alignment2(io_ctx.ptr, &mut requires) };
         check_error(code)?;
-        Ok(ListObjectResultIter {
-            list_ctx,
-            pool: &self,
-        })
+        Ok(requires != 0)
     }
 
-    pub fn stat(&self) -> Result<Stat, Error> {
+    /// The stripe width of the pool, which appends must be aligned to when
+    /// `requires_alignment` returns true.
+    pub fn required_alignment(&self) -> Result<u64, Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-        let mut stat = rados_pool_stat_t {
-            num_bytes: 0,
-            num_kb: 0,
-            num_objects: 0,
-            num_object_clones: 0,
-            num_object_copies: 0,
-            num_objects_missing_on_primary: 0,
-            num_objects_unfound: 0,
-            num_objects_degraded: 0,
-            num_rd: 0,
-            num_rd_kb: 0,
-            num_wr: 0,
-            num_wr_kb: 0,
-            num_user_bytes: 0,
-            compressed_bytes_orig: 0,
-            compressed_bytes: 0,
-            compressed_bytes_alloc: 0,
-        };
-        let code = unsafe { rados_ioctx_pool_stat(io_ctx.ptr, &mut stat) };
+        let mut alignment: u64 = 0;
+
+        let code = unsafe { rados_ioctx_pool_required_alignment2(io_ctx.ptr, &mut alignment) };
         check_error(code)?;
+        Ok(alignment)
+    }
+
+    pub async fn snapshot_create(&self, snap_name: &str) -> Result<(), Error> {
+        let snap_name = snap_name.to_string();
+        let (rados, pool_name) = (self.rados.detach(), self.name.clone());
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            let snap_name = std::ffi::CString::new(snap_name)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+            let code =
+                unsafe { rados_ioctx_snap_create(io_ctx.ptr, snap_name.as_ptr() as *const c_char) };
+            assert!(code <= 0);
+            check_error(code)
+        })
+        .await
+    }
+
+    pub async fn snapshot_remove(&self, snap_name: &str) -> Result<(), Error> {
+        let snap_name = snap_name.to_string();
+        let (rados, pool_name) = (self.rados.detach(), self.name.clone());
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            let snap_name = std::ffi::CString::new(snap_name)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+            let code =
+                unsafe { rados_ioctx_snap_remove(io_ctx.ptr, snap_name.as_ptr() as *const c_char) };
+            assert!(code <= 0);
+            check_error(code)
+        })
+        .await
+    }
+
+    pub async fn snapshot_rollback_for(
+        &self,
+        object: &Object<'_>,
+        snap_name: &str,
+    ) -> Result<(), Error> {
+        let snap_id = self.snapshot_lookup(snap_name).await?;
+        self.rollback_object(object, snap_id).await
+    }
+
+    pub async fn snapshot_list(&self) -> Result<Vec<SnapId>, Error> {
+        let (rados, pool_name) = (self.rados.detach(), self.name.clone());
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            let mut snap_ids: Vec<SnapId> = Vec::with_capacity(16);
+            let max_len: usize = (1 << 31) - 1;
+
+            while snap_ids.capacity() <= max_len {
+                let code = unsafe {
+                    rados_ioctx_snap_list(
+                        io_ctx.ptr,
+                        snap_ids.as_mut_ptr() as *mut u64,
+                        snap_ids.capacity() as i32,
+                    )
+                };
+                if code >= 0 {
+                    unsafe {
+                        snap_ids.set_len(code as usize);
+                    }
+                    return Ok(snap_ids);
+                }
+                match { check_error(code) } {
+                    Err(e) => {
+                        if e == *ERROR_RANGE {
+                            snap_ids.reserve(snap_ids.capacity() * 2);
+                        } else {
+                            return Err(e);
+                        }
+                    }
+                    Ok(_) => {
+                        return Err(OtherError(anyhow!("unexpected success")));
+                    }
+                }
+            }
+            Err(OtherError(anyhow!("too many snapshots")))
+        })
+        .await
+    }
+
+    pub async fn snapshot_lookup(&self, snap_name: &str) -> Result<SnapId, Error> {
+        let snap_name = snap_name.to_string();
+        let (rados, pool_name) = (self.rados.detach(), self.name.clone());
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            let snap_name = CString::new(snap_name)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            let mut snap_id: u64 = 0;
+
+            let code = unsafe {
+                rados_ioctx_snap_lookup(
+                    io_ctx.ptr,
+                    snap_name.as_ptr() as *const c_char,
+                    &mut snap_id as *mut u64,
+                )
+            };
+            assert!(code <= 0);
+            check_error(code)?;
+            Ok(SnapId::new(snap_id))
+        })
+        .await
+    }
 
-        Ok(Stat {
-            num_bytes: stat.num_bytes,
-            num_kb: stat.num_kb,
-            num_objects: stat.num_objects,
-            num_object_clones: stat.num_object_clones,
-            num_object_copies: stat.num_object_copies,
-            num_objects_missing_on_primary: stat.num_objects_missing_on_primary,
-            num_objects_unfound: stat.num_objects_unfound,
-            num_objects_degraded: stat.num_objects_degraded,
-            num_rd: stat.num_rd,
-            num_rd_kb: stat.num_rd_kb,
-            num_wr: stat.num_wr,
-            num_wr_kb: stat.num_wr_kb,
-            num_user_bytes: stat.num_user_bytes,
-            compressed_bytes_orig: stat.compressed_bytes_orig,
-            compressed_bytes: stat.compressed_bytes,
-            compressed_bytes_alloc: stat.compressed_bytes_alloc,
+    pub async fn snapshot_get_name(&self, snap_id: SnapId) -> Result<String, Error> {
+        let (rados, pool_name) = (self.rados.detach(), self.name.clone());
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            let mut len: usize = 256;
+
+            loop {
+                let mut snap_name: Vec<u8> = vec![0; len];
+                let code = unsafe {
+                    rados_ioctx_snap_get_name(
+                        io_ctx.ptr,
+                        snap_id.id(),
+                        snap_name.as_mut_ptr() as *mut c_char,
+                        snap_name.len() as i32,
+                    )
+                };
+                match check_error(code) {
+                    Ok(_) => {
+                        if let Some(pos) = snap_name.iter().position(|&x| x == 0) {
+                            snap_name.truncate(pos);
+                        }
+                        return Ok(String::from_utf8_lossy(&snap_name).to_string());
+                    }
+                    // librados doesn't report the length it needs
+                    Err(e) if e == *ERROR_RANGE && len < MAX_BUF_SIZE => len *= 2,
+                    Err(e) => return Err(e),
+                }
+            }
+        })
+        .await
+    }
+
+    pub async fn snapshot_get_stamp(&self, snap_id: SnapId) -> Result<SystemTime, Error> {
+        let (rados, pool_name) = (self.rados.detach(), self.name.clone());
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            let mut stamp: time_t = 0;
+
+            let code = unsafe { rados_ioctx_snap_get_stamp(io_ctx.ptr, snap_id.id(), &mut stamp) };
+            check_error(code)?;
+            Ok(UNIX_EPOCH + Duration::from_secs(stamp as u64))
         })
+        .await
     }
 
-    pub fn snapshot_create(&self, snap_name: &str) -> Result<(), Error> {
+    /// All pool snapshots with their names and creation times.
+    pub async fn snapshots(&self) -> Result<Vec<Snapshot>, Error> {
+        let mut snapshots = Vec::new();
+        for id in self.snapshot_list().await? {
+            snapshots.push(Snapshot {
+                id,
+                name: self.snapshot_get_name(id).await?,
+                created: self.snapshot_get_stamp(id).await?,
+            });
+        }
+        Ok(snapshots)
+    }
+
+    /// Roll every object in the pool namespace (see `set_namespace`) back to
+    /// snapshot `snap_id`, keeping at most `concurrency` rollbacks in flight.
+    /// This covers the objects in the namespace now and in the snapshot, so
+    /// objects removed since are restored and ones created since are removed.
+    /// `progress` is called with the name of each object once it is rolled
+    /// back and the number of objects done so far. Returns the number of
+    /// objects rolled back.
+    pub async fn rollback_all<F>(
+        &self,
+        snap_id: SnapId,
+        concurrency: usize,
+        mut progress: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(&str, usize),
+    {
+        let snapshot = self.at_snapshot(snap_id);
+        let (head, snapped) =
+            futures::try_join!(self.list_object_names(), snapshot.list_object_names())?;
+        let names: BTreeSet<String> = head.into_iter().chain(snapped).collect();
+
+        let mut rollbacks = stream::iter(names)
+            .map(|name| async move {
+                self.rollback_object(&self.object(&name), snap_id).await?;
+                Ok::<_, Error>(name)
+            })
+            .buffer_unordered(concurrency.max(1));
+
+        let mut done = 0;
+        while let Some(name) = rollbacks.next().await {
+            let name = name?;
+            done += 1;
+            progress(&name, done);
+        }
+        Ok(done)
+    }
+
+    // roll a single object back, works for both pool and self-managed snapshots
+    async fn rollback_object(&self, object: &Object<'_>, snap_id: SnapId) -> Result<(), Error> {
+        let io_ctx = self.io_ctx()?;
+        io_ctx.set_namespace(object.namespace())?;
+        let op = WriteOp::new()?;
+        unsafe { rados_write_op_rollback(op.ptr, snap_id.id()) };
+        op.operate(&io_ctx, object.name()).await?;
+        Ok(())
+    }
+
+    /// Stream the objects in the pool namespace (see `set_namespace`) that
+    /// differ between snapshots `a` and `b`. For each object the cheapest
+    /// difference found is reported, checking in order: existence, size,
+    /// mtime, xattrs and finally a checksum of the content computed on the
+    /// OSDs, so object data is not transferred. Both snapshots are listed
+    /// when the stream is first polled, and listing errors are its first item.
+    pub fn diff_snapshots(
+        &self,
+        a: SnapId,
+        b: SnapId,
+    ) -> Result<impl Stream<Item = Result<ObjectDiff, Error>> + Send + 'a, Error> {
+        let snaps = Arc::new((self.at_snapshot(a), self.at_snapshot(b)));
+        let names = {
+            let snaps = snaps.clone();
+            async move {
+                let (a, b) =
+                    futures::try_join!(snaps.0.list_object_names(), snaps.1.list_object_names())?;
+                let names: BTreeSet<String> = a.into_iter().chain(b).collect();
+                Ok::<_, Error>(stream::iter(names).map(Ok))
+            }
+        };
+
+        Ok(stream::once(names)
+            .try_flatten()
+            .try_filter_map(move |name| {
+                let snaps = snaps.clone();
+                async move { diff_object(&snaps.0, &snaps.1, name).await }
+            }))
+    }
+
+    /// Allocate a self-managed snapshot id. The snapshot only takes effect for
+    /// writes tagged with a `SnapContext` that includes it.
+    pub async fn selfmanaged_snap_create(&self) -> Result<SnapId, Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-        let snap_name = std::ffi::CString::new(snap_name)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let comp = AioCompletion::new()?;
+        let mut snap_id = Box::new(0u64);
 
-        let code =
-            unsafe { rados_ioctx_snap_create(io_ctx.ptr, snap_name.as_ptr() as *const c_char) };
-        assert!(code <= 0);
-        check_error(code)
+        // returns void; failures come back through the completion, which
+        // also owns the id librados fills in
+        unsafe { rados_aio_ioctx_selfmanaged_snap_create(io_ctx.ptr, snap_id.as_mut(), comp.ptr) };
+        let (_, snap_id) = BufferedCompletion::new(comp, snap_id).await?;
+
+        Ok(SnapId::new(*snap_id))
     }
 
-    pub fn snapshot_remove(&self, snap_name: &str) -> Result<(), Error> {
+    pub async fn selfmanaged_snap_remove(&self, snap_id: SnapId) -> Result<(), Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-        let snap_name = std::ffi::CString::new(snap_name)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let comp = AioCompletion::new()?;
+
+        // returns void; failures come back through the completion
+        unsafe { rados_aio_ioctx_selfmanaged_snap_remove(io_ctx.ptr, snap_id.id(), comp.ptr) };
+        comp.await?;
+
+        Ok(())
+    }
+
+    /// Roll `object` back to self-managed snapshot `snap_id`. The rollback is
+    /// itself a write and is tagged with the pool snap context.
+    pub async fn selfmanaged_snap_rollback(
+        &self,
+        object: &Object<'_>,
+        snap_id: SnapId,
+    ) -> Result<(), Error> {
+        self.rollback_object(object, snap_id).await
+    }
+
+    /// Tag the pool with `application`. `force` is required to add a second
+    /// application to a pool that is already tagged.
+    pub fn application_enable(&self, application: &Application, force: bool) -> Result<(), Error> {
+        let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
+        let app_name = CString::new(application.name())?;
 
         let code =
-            unsafe { rados_ioctx_snap_remove(io_ctx.ptr, snap_name.as_ptr() as *const c_char) };
-        assert!(code <= 0);
+            unsafe { rados_application_enable(io_ctx.ptr, app_name.as_ptr(), force as c_int) };
         check_error(code)
     }
 
-    pub fn snapshot_rollback_for(&self, object: &Object, snap_name: &str) -> Result<(), Error> {
+    pub fn application_list(&self) -> Result<Vec<Application>, Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-        let object_name = std::ffi::CString::new(object.name())
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        let snap_name = std::ffi::CString::new(snap_name)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let mut len: usize = 256;
 
-        let code = unsafe {
-            rados_ioctx_snap_rollback(
-                io_ctx.ptr,
-                object_name.as_ptr(),
-                snap_name.as_ptr() as *const c_char,
-            )
-        };
-        assert!(code <= 0);
-        check_error(code)
+        loop {
+            let mut buf: Vec<u8> = vec![0; len];
+            let code = unsafe {
+                rados_application_list(io_ctx.ptr, buf.as_mut_ptr() as *mut c_char, &mut len)
+            };
+            match check_error(code) {
+                Ok(_) => {
+                    buf.truncate(len);
+                    return Ok(split_nul_terminated(&buf)
+                        .iter()
+                        .map(|name| Application::from(name.as_str()))
+                        .collect());
+                }
+                // len now holds the required size
+                Err(e) if e == *ERROR_RANGE && len > buf.len() => continue,
+                Err(e) => return Err(e),
+            }
+        }
     }
 
-    pub fn snapshot_list(&self) -> Result<Vec<u64>, Error> {
+    pub fn application_metadata_get(
+        &self,
+        application: &Application,
+        key: &str,
+    ) -> Result<String, Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-        let mut snap_ids: Vec<u64> = Vec::with_capacity(16);
-        let max_len: usize = (1 << 31) - 1;
+        let app_name = CString::new(application.name())?;
+        let key = CString::new(key)?;
+        let mut len: usize = 256;
 
-        while snap_ids.capacity() <= max_len {
+        loop {
+            let mut buf: Vec<u8> = vec![0; len];
             let code = unsafe {
-                rados_ioctx_snap_list(
+                rados_application_metadata_get(
                     io_ctx.ptr,
-                    snap_ids.as_mut_ptr(),
-                    snap_ids.capacity() as i32,
+                    app_name.as_ptr(),
+                    key.as_ptr(),
+                    buf.as_mut_ptr() as *mut c_char,
+                    &mut len,
                 )
             };
-            if code >= 0 {
-                unsafe {
-                    snap_ids.set_len(code as usize);
-                }
-                return Ok(snap_ids);
-            }
-            match { check_error(code) } {
-                Err(e) => {
-                    if e == *ERROR_RANGE {
-                        snap_ids.reserve(snap_ids.capacity() * 2);
-                    } else {
-                        return Err(e);
-                    }
-                }
+            match check_error(code) {
                 Ok(_) => {
-                    return Err(OtherError(anyhow!("unexpected success")));
+                    buf.truncate(len);
+                    return Ok(split_nul_terminated(&buf).pop().unwrap_or_default());
                 }
+                Err(e) if e == *ERROR_RANGE && len > buf.len() => continue,
+                Err(e) => return Err(e),
             }
         }
-        Err(OtherError(anyhow!("too many snapshots")))
     }
 
-    pub fn snapshot_lookup(&self, snap_name: &str) -> Result<u64, Error> {
+    pub fn application_metadata_set(
+        &self,
+        application: &Application,
+        key: &str,
+        value: &str,
+    ) -> Result<(), Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-        let snap_name = CString::new(snap_name)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        let mut snap_id: u64 = 0;
+        let app_name = CString::new(application.name())?;
+        let key = CString::new(key)?;
+        let value = CString::new(value)?;
 
         let code = unsafe {
-            rados_ioctx_snap_lookup(
+            rados_application_metadata_set(
                 io_ctx.ptr,
-                snap_name.as_ptr() as *const c_char,
-                &mut snap_id as *mut u64,
+                app_name.as_ptr(),
+                key.as_ptr(),
+                value.as_ptr(),
             )
         };
-        assert!(code <= 0);
-        check_error(code)?;
-        Ok(snap_id)
+        check_error(code)
     }
 
-    pub fn snapshot_get_name(&self, snap_id: u64) -> Result<String, Error> {
+    pub fn application_metadata_remove(
+        &self,
+        application: &Application,
+        key: &str,
+    ) -> Result<(), Error> {
         let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
-
-        let max_len = 256;
-        let mut snap_name = Vec::with_capacity(max_len);
+        let app_name = CString::new(application.name())?;
+        let key = CString::new(key)?;
 
         let code = unsafe {
-            rados_ioctx_snap_get_name(
-                io_ctx.ptr,
-                snap_id,
-                snap_name.as_mut_ptr() as *mut c_char,
-                snap_name.capacity() as i32,
-            )
+            rados_application_metadata_remove(io_ctx.ptr, app_name.as_ptr(), key.as_ptr())
         };
-        check_error(code).map(|_| {
-            unsafe {
-                snap_name.set_len(snap_name.capacity());
+        check_error(code)
+    }
+
+    pub fn application_metadata_list(
+        &self,
+        application: &Application,
+    ) -> Result<HashMap<String, String>, Error> {
+        let io_ctx = IoCtx::new(self.rados, self.name.clone())?;
+        let app_name = CString::new(application.name())?;
+        let mut keys_len: usize = 256;
+        let mut values_len: usize = 256;
+
+        loop {
+            let mut keys: Vec<u8> = vec![0; keys_len];
+            let mut values: Vec<u8> = vec![0; values_len];
+            let code = unsafe {
+                rados_application_metadata_list(
+                    io_ctx.ptr,
+                    app_name.as_ptr(),
+                    keys.as_mut_ptr() as *mut c_char,
+                    &mut keys_len,
+                    values.as_mut_ptr() as *mut c_char,
+                    &mut values_len,
+                )
+            };
+            match check_error(code) {
+                Ok(_) => {
+                    keys.truncate(keys_len);
+                    values.truncate(values_len);
+                    return Ok(split_nul_terminated(&keys)
+                        .into_iter()
+                        .zip(split_nul_terminated(&values))
+                        .collect());
+                }
+                Err(e)
+                    if e == *ERROR_RANGE
+                        && (keys_len > keys.len() || values_len > values.len()) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
             }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Application {
+    Rbd,
+    Rgw,
+    CephFs,
+    Custom(String),
+}
+
+impl Application {
+    pub fn name(&self) -> &str {
+        match self {
+            Application::Rbd => "rbd",
+            Application::Rgw => "rgw",
+            Application::CephFs => "cephfs",
+            Application::Custom(name) => name,
+        }
+    }
+}
 
-            if let Some(pos) = snap_name.iter().position(|&x| x == 0) {
-                String::from_utf8_lossy(&snap_name[..pos]).to_string()
+impl From<&str> for Application {
+    fn from(name: &str) -> Self {
+        match name {
+            "rbd" => Application::Rbd,
+            "rgw" => Application::Rgw,
+            "cephfs" => Application::CephFs,
+            name => Application::Custom(name.to_string()),
+        }
+    }
+}
+
+impl Display for Application {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A read-only view of a pool as of a snapshot, see `Pool::at_snapshot`.
+#[derive(Debug)]
+pub struct PoolSnapshot<'a> {
+    name: String,
+    namespace: String,
+    rados: &'a Rados,
+    snap_id: SnapId,
+}
+
+impl<'a> PoolSnapshot<'a> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn snap_id(&self) -> SnapId {
+        self.snap_id
+    }
+
+    /// Handle to an object as of this snapshot, without checking it exists.
+    pub fn object(&self, name: &str) -> Object<'a, ReadOnly> {
+        Object::new_at(self.rados, &self.name, name, self.snap_id).with_namespace(&self.namespace)
+    }
+
+    pub async fn get_object(&self, name: &str) -> Result<Object<'a, ReadOnly>, Error> {
+        let obj = self.object(name);
+        if !obj.exists().await? {
+            return Err(check_error(-2).unwrap_err());
+        }
+        Ok(obj)
+    }
+
+    pub fn list_objects(&self) -> Result<ListObjectResultIter<'a, ReadOnly>, Error> {
+        ListObjectResultIter::open(self.rados, &self.name, &self.namespace, self.snap_id)
+    }
+
+    /// Names of the objects in the snapshot, see `Pool::list_object_names`.
+    pub async fn list_object_names(&self) -> Result<Vec<String>, Error> {
+        list_names(self.rados, &self.name, &self.namespace, self.snap_id).await
+    }
+}
+
+async fn diff_object(
+    before: &PoolSnapshot<'_>,
+    after: &PoolSnapshot<'_>,
+    name: String,
+) -> Result<Option<ObjectDiff>, Error> {
+    let old = before.object(&name);
+    let new = after.object(&name);
+
+    let change = match (old.stat_if_exists().await?, new.stat_if_exists().await?) {
+        (None, None) => None,
+        (None, Some(_)) => Some(ObjectChange::Added),
+        (Some(_), None) => Some(ObjectChange::Removed),
+        (Some(o), Some(n)) if o.size != n.size => Some(ObjectChange::Size),
+        (Some(o), Some(n)) if o.mtime != n.mtime => Some(ObjectChange::Mtime),
+        (Some(o), Some(_)) => {
+            if old.get_xattrs().await? != new.get_xattrs().await? {
+                Some(ObjectChange::Xattrs)
+            } else if !same_content(&old, &new, o.size).await? {
+                Some(ObjectChange::Content)
             } else {
-                String::from_utf8_lossy(&snap_name).to_string()
+                None
             }
-        })
+        }
+    };
+
+    Ok(change.map(|change| ObjectDiff { name, change }))
+}
+
+async fn same_content(
+    a: &Object<'_, ReadOnly>,
+    b: &Object<'_, ReadOnly>,
+    size: u64,
+) -> Result<bool, Error> {
+    if size == 0 {
+        return Ok(true);
     }
+
+    // one xxhash64 value per object, only the checksums cross the network
+    let len = size as usize;
+    let (sum_a, sum_b) = futures::try_join!(
+        a.checksum(ChecksumKind::XxHash64, 0, len, 0),
+        b.checksum(ChecksumKind::XxHash64, 0, len, 0)
+    )?;
+    Ok(sum_a == sum_b)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolInfo {
+    pub id: i64,
+    pub name: String,
+    /// Id of the base pool when this pool is a cache tier, otherwise its own id.
+    pub base_tier: i64,
+    pub applications: Vec<Application>,
 }
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -253,16 +809,74 @@ pub struct Stat {
     pub compressed_bytes_alloc: u64,
 }
 
-pub struct ListObjectResultIter<'a> {
-    list_ctx: rados_list_ctx_t,
+pub struct ListObjectResultIter<'a, A = ReadWrite> {
+    list: ListCtx<'a>,
+    rados: &'a Rados,
+    pool_name: String,
+    snap: SnapId,
+    snap_context: Option<SnapContext>,
+    _access: PhantomData<A>,
+}
 
-    pool: &'a Pool<'a>,
+impl<'a, A> ListObjectResultIter<'a, A> {
+    fn open(
+        rados: &'a Rados,
+        pool_name: &str,
+        namespace: &str,
+        snap: SnapId,
+    ) -> Result<Self, Error> {
+        let io_ctx = IoCtx::new(rados, pool_name.to_string())?;
+        Ok(ListObjectResultIter {
+            list: ListCtx::open(io_ctx, namespace, snap)?,
+            rados,
+            pool_name: pool_name.to_string(),
+            snap,
+            snap_context: None,
+            _access: PhantomData,
+        })
+    }
 }
 
-impl<'a> Iterator for ListObjectResultIter<'a> {
-    type Item = Object<'a>;
+impl<'a, A> Iterator for ListObjectResultIter<'a, A> {
+    type Item = Object<'a, A>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let (entry, nspace) = blocking_in_place(|| self.list.next_entry())?;
+        Some(
+            Object::new_at(self.rados, &self.pool_name, &entry, self.snap)
+                .with_namespace(&nspace)
+                .with_snap_context(self.snap_context.clone()),
+        )
+    }
+}
+
+// A librados list context, with the io context it borrows.
+struct ListCtx<'a> {
+    ptr: rados_list_ctx_t,
+    _io_ctx: IoCtx<'a>,
+}
+
+impl<'a> ListCtx<'a> {
+    fn open(io_ctx: IoCtx<'a>, namespace: &str, snap: SnapId) -> Result<Self, Error> {
+        if !namespace.is_empty() {
+            io_ctx.set_namespace(namespace)?;
+        }
+        if !snap.is_head() {
+            io_ctx.set_read_snap(snap);
+        }
+        let mut ptr = null_mut();
+
+        let code = unsafe { rados_nobjects_list_open(io_ctx.ptr, &mut ptr) };
+        check_error(code)?;
+        Ok(ListCtx {
+            ptr,
+            _io_ctx: io_ctx,
+        })
+    }
+
+    // name and namespace of the next object; fetches the next batch of
+    // entries from the OSDs, blocking, when the last one is used up
+    fn next_entry(&mut self) -> Option<(String, String)> {
         let mut entry = null();
         let mut key = null();
         let mut nspace = null();
@@ -272,7 +886,7 @@ impl<'a> Iterator for ListObjectResultIter<'a> {
 
         let code = unsafe {
             rados_nobjects_list_next2(
-                self.list_ctx,
+                self.ptr,
                 &mut entry,
                 &mut key,
                 &mut nspace,
@@ -283,26 +897,46 @@ impl<'a> Iterator for ListObjectResultIter<'a> {
         };
         check_error(code).map_or(None, |_| unsafe {
             let entry = c_char_ptr_to_string(entry, entry_size);
-            #[allow(unused_variables)]
-            let key = c_char_ptr_to_string(key, key_size);
-            #[allow(unused_variables)]
             let nspace = c_char_ptr_to_string(nspace, nspace_size);
-            Some(Object::new(self.pool.rados, &self.pool.name, &entry))
+            Some((entry, nspace))
         })
     }
 }
 
-impl Drop for ListObjectResultIter<'_> {
+impl Drop for ListCtx<'_> {
     fn drop(&mut self) {
         unsafe {
-            rados_nobjects_list_close(self.list_ctx);
+            rados_nobjects_list_close(self.ptr);
         }
     }
 }
 
+// Names of the objects in `namespace` of the pool as of `snap`, listed on the
+// blocking thread pool, see `blocking`.
+async fn list_names(
+    rados: &Rados,
+    pool_name: &str,
+    namespace: &str,
+    snap: SnapId,
+) -> Result<Vec<String>, Error> {
+    let rados = rados.detach();
+    let (pool_name, namespace) = (pool_name.to_string(), namespace.to_string());
+    blocking(move || {
+        let io_ctx = IoCtx::detached(&rados, pool_name)?;
+        let mut list = ListCtx::open(io_ctx, &namespace, snap)?;
+        let mut names = Vec::new();
+        while let Some((name, _)) = list.next_entry() {
+            names.push(name);
+        }
+        Ok(names)
+    })
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cluster::Cluster;
     use crate::errors::Error::RadosError;
     use crate::rados::Rados;
 
@@ -323,30 +957,190 @@ mod tests {
         futures::future::join_all(stats).await;
     }
 
-    #[tokio::test]
+    #[tokio::test(flavor = "multi_thread")]
     async fn test_pool_stat() {
         let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
         let pool = Pool::new(&rados, "test");
 
-        let stat = pool.stat().unwrap();
+        let stat = pool.stat().await.unwrap();
         println!("{:?}", stat);
     }
 
+    #[tokio::test]
+    async fn test_create_object_exclusive() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let _ = pool.remove_object("test-create-exclusive").await;
+
+        let obj = pool.object("test-create-exclusive");
+        assert!(!obj.exists().await.unwrap());
+        assert!(pool.get_object("test-create-exclusive").await.is_err());
+
+        let obj = pool
+            .create_object_exclusive("test-create-exclusive")
+            .await
+            .unwrap();
+        assert!(obj.exists().await.unwrap());
+        obj.write_full(b"data").await.unwrap();
+
+        match pool.create_object_exclusive("test-create-exclusive").await {
+            Err(Error::AlreadyExists(name)) => assert_eq!(name, "test-create-exclusive"),
+            other => panic!("expected AlreadyExists, got {:?}", other.map(|_| ())),
+        }
+        // a plain create leaves the existing data alone
+        pool.create_object("test-create-exclusive").await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, 4);
+
+        pool.remove_object("test-create-exclusive").await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_pool_snapshot() {
         let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
         let pool = Pool::new(&rados, "test");
 
-        let snaps = pool.snapshot_list().unwrap();
+        let snaps = pool.snapshot_list().await.unwrap();
         println!("{:?}", snaps);
     }
 
+    #[tokio::test]
+    async fn test_pool_snapshots() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        if pool.snapshot_lookup("snap1").await.is_ok() {
+            pool.snapshot_remove("snap1").await.unwrap();
+        }
+        let before = SystemTime::now() - Duration::from_secs(60);
+        pool.snapshot_create("snap1").await.unwrap();
+
+        let snaps = pool.snapshots().await.unwrap();
+        let snap = snaps.iter().find(|snap| snap.name == "snap1").unwrap();
+        assert_eq!(snap.id, pool.snapshot_lookup("snap1").await.unwrap());
+        assert!(snap.created > before);
+
+        pool.snapshot_remove("snap1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pool_rollback_all() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        if pool.snapshot_lookup("snap1").await.is_ok() {
+            pool.snapshot_remove("snap1").await.unwrap();
+        }
+        let _ = pool.remove_object("rollback-added").await;
+
+        let obj1 = pool.put_object("obj1", b"test1").await.unwrap();
+        let obj2 = pool.put_object("obj2", b"test1").await.unwrap();
+        let removed = pool.put_object("rollback-removed", b"test1").await.unwrap();
+        pool.snapshot_create("snap1").await.unwrap();
+        obj1.write_full(b"test2").await.unwrap();
+        obj2.write_full(b"test2").await.unwrap();
+        removed.remove().await.unwrap();
+        let added = pool.put_object("rollback-added", b"test2").await.unwrap();
+
+        let snap_id = pool.snapshot_lookup("snap1").await.unwrap();
+        let mut names = Vec::new();
+        let count = pool
+            .rollback_all(snap_id, 4, |name, done| {
+                names.push(name.to_string());
+                assert_eq!(names.len(), done);
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, names.len());
+        assert!(names.iter().any(|name| name == "rollback-removed"));
+        assert!(names.iter().any(|name| name == "rollback-added"));
+
+        let mut buffer = [0; 8];
+        let len = obj1.read(0, &mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"test1");
+        let len = obj2.read(0, &mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"test1");
+        let len = removed.read(0, &mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"test1");
+        assert!(!added.exists().await.unwrap());
+
+        removed.remove().await.unwrap();
+        pool.snapshot_remove("snap1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pool_diff_snapshots() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        for snap in ["snap1", "snap2"] {
+            if pool.snapshot_lookup(snap).await.is_ok() {
+                pool.snapshot_remove(snap).await.unwrap();
+            }
+        }
+        let _ = pool.remove_object("diff-added").await;
+        let mut other = Pool::new(&rados, "test");
+        other.set_namespace("diff-ns");
+        let _ = other.remove_object("diff-ns-added").await;
+
+        let resized = pool.put_object("diff-resized", b"test1").await.unwrap();
+        let xattrs = pool.put_object("diff-xattrs", b"test1").await.unwrap();
+        pool.put_object("diff-same", b"test1").await.unwrap();
+        pool.snapshot_create("snap1").await.unwrap();
+
+        resized.write_full(b"test22").await.unwrap();
+        xattrs.set_xattr("key", b"value").await.unwrap();
+        pool.put_object("diff-added", b"test1").await.unwrap();
+        other.put_object("diff-ns-added", b"test1").await.unwrap();
+        pool.snapshot_create("snap2").await.unwrap();
+        let snap1 = pool.snapshot_lookup("snap1").await.unwrap();
+        let snap2 = pool.snapshot_lookup("snap2").await.unwrap();
+
+        // the stream can be handed to a spawned task
+        let diffs = pool.diff_snapshots(snap1, snap2).unwrap();
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&diffs);
+        let diffs: Vec<ObjectDiff> = diffs.map(|diff| diff.unwrap()).collect().await;
+
+        let change = |name: &str| {
+            diffs
+                .iter()
+                .find(|diff| diff.name == name)
+                .map(|diff| diff.change)
+        };
+        assert_eq!(change("diff-added"), Some(ObjectChange::Added));
+        assert_eq!(change("diff-resized"), Some(ObjectChange::Size));
+        assert!(matches!(
+            change("diff-xattrs"),
+            Some(ObjectChange::Xattrs) | Some(ObjectChange::Mtime)
+        ));
+        assert_eq!(change("diff-same"), None);
+        assert_eq!(change("diff-ns-added"), None);
+
+        // only the objects in the namespace are compared
+        let diffs: Vec<ObjectDiff> = other
+            .diff_snapshots(snap1, snap2)
+            .unwrap()
+            .map(|diff| diff.unwrap())
+            .collect()
+            .await;
+        assert!(diffs
+            .iter()
+            .any(|diff| diff.name == "diff-ns-added" && diff.change == ObjectChange::Added));
+        assert!(diffs
+            .iter()
+            .all(|diff| !diff.name.starts_with("diff-resized")));
+        other.remove_object("diff-ns-added").await.unwrap();
+
+        pool.snapshot_remove("snap1").await.unwrap();
+        pool.snapshot_remove("snap2").await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_pool_snapshot_lookup() {
         let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
         let pool = Pool::new(&rados, "test");
 
-        let snap_id = pool.snapshot_lookup("snap1").unwrap();
+        let snap_id = pool.snapshot_lookup("snap1").await.unwrap();
         println!("{:?}", snap_id);
     }
 
@@ -355,7 +1149,7 @@ mod tests {
         let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
         let pool = Pool::new(&rados, "test");
 
-        let snap_name = pool.snapshot_get_name(1).unwrap();
+        let snap_name = pool.snapshot_get_name(SnapId::new(1)).await.unwrap();
         println!("{:?}", snap_name);
     }
 
@@ -364,18 +1158,18 @@ mod tests {
         let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
         let pool = Pool::new(&rados, "test");
 
-        match pool.snapshot_lookup("snap1") {
+        match pool.snapshot_lookup("snap1").await {
             Ok(_) => {
-                pool.snapshot_remove("snap1").unwrap();
+                pool.snapshot_remove("snap1").await.unwrap();
             }
             Err(_) => {}
         }
 
-        pool.snapshot_create("snap1").unwrap();
-        pool.snapshot_remove("snap1").unwrap();
+        pool.snapshot_create("snap1").await.unwrap();
+        pool.snapshot_remove("snap1").await.unwrap();
 
         assert_eq!(
-            pool.snapshot_lookup("snap1").unwrap_err(),
+            pool.snapshot_lookup("snap1").await.unwrap_err(),
             RadosError((-2, "No such file or directory".to_string()))
         );
     }
@@ -385,15 +1179,15 @@ mod tests {
         let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
         let pool = Pool::new(&rados, "test");
 
-        match pool.snapshot_lookup("snap1") {
+        match pool.snapshot_lookup("snap1").await {
             Ok(_) => {
-                pool.snapshot_remove("snap1").unwrap();
+                pool.snapshot_remove("snap1").await.unwrap();
             }
             Err(_) => {}
         }
 
         let obj = pool.put_object("obj1", b"test1").await.unwrap();
-        pool.snapshot_create("snap1").unwrap();
+        pool.snapshot_create("snap1").await.unwrap();
         obj.write_full(b"test2").await.unwrap();
 
         let mut buffer = [0; 8];
@@ -401,12 +1195,109 @@ mod tests {
         assert_eq!(len, 5);
         assert_eq!(&buffer[..len], b"test2");
 
-        pool.snapshot_rollback_for(&obj, "snap1").unwrap();
+        pool.snapshot_rollback_for(&obj, "snap1").await.unwrap();
 
         let len = obj.read(0, &mut buffer).await.unwrap();
         assert_eq!(len, 5);
         assert_eq!(&buffer[..len], b"test1");
 
-        pool.snapshot_remove("snap1").unwrap();
+        pool.snapshot_remove("snap1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pool_at_snapshot() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        if pool.snapshot_lookup("snap1").await.is_ok() {
+            pool.snapshot_remove("snap1").await.unwrap();
+        }
+
+        let obj = pool.put_object("obj1", b"test1").await.unwrap();
+        pool.snapshot_create("snap1").await.unwrap();
+        obj.write_full(b"test22").await.unwrap();
+
+        let snap = pool.at_snapshot(pool.snapshot_lookup("snap1").await.unwrap());
+        let snap_obj = snap.get_object("obj1").await.unwrap();
+        assert_eq!(snap_obj.stat().await.unwrap().size, 5);
+
+        let mut buffer = [0; 8];
+        let len = snap_obj.read(0, &mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"test1");
+        assert!(snap.list_objects().unwrap().any(|o| o.name() == "obj1"));
+
+        let len = obj.read(0, &mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"test22");
+
+        pool.snapshot_remove("snap1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pool_selfmanaged_snapshot() {
+        let cluster = Cluster::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        if cluster.pool_lookup("test-selfmanaged").is_err() {
+            cluster.pool_create("test-selfmanaged").unwrap();
+        }
+        let mut pool = cluster.pool_lookup("test-selfmanaged").unwrap();
+
+        let mut ctx = SnapContext::default();
+        pool.set_snap_context(Some(ctx.clone()));
+        let obj = pool.put_object("obj1", b"test1").await.unwrap();
+
+        let snap_id = pool.selfmanaged_snap_create().await.unwrap();
+        ctx = ctx.with_snap(snap_id);
+        pool.set_snap_context(Some(ctx.clone()));
+
+        let obj = pool.get_object(obj.name()).await.unwrap();
+        obj.write_full(b"test2").await.unwrap();
+
+        let mut buffer = [0; 8];
+        let snap_obj = pool.at_snapshot(snap_id).get_object("obj1").await.unwrap();
+        let len = snap_obj.read(0, &mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"test1");
+
+        pool.selfmanaged_snap_rollback(&obj, snap_id).await.unwrap();
+        let len = obj.read(0, &mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"test1");
+
+        pool.selfmanaged_snap_remove(snap_id).await.unwrap();
+        pool.set_snap_context(Some(ctx.without_snap(snap_id)));
+        pool.remove_object("obj1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pool_alignment() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        if pool.requires_alignment().unwrap() {
+            assert!(pool.required_alignment().unwrap() > 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_application_metadata() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let app = Application::Custom("librados-rs".to_string());
+
+        pool.application_enable(&app, true).unwrap();
+        assert!(pool.application_list().unwrap().contains(&app));
+
+        pool.application_metadata_set(&app, "key1", "value1")
+            .unwrap();
+        pool.application_metadata_set(&app, "key2", "").unwrap();
+        assert_eq!(
+            pool.application_metadata_get(&app, "key1").unwrap(),
+            "value1"
+        );
+
+        let metadata = pool.application_metadata_list(&app).unwrap();
+        assert_eq!(metadata.get("key1").map(String::as_str), Some("value1"));
+        assert_eq!(metadata.get("key2").map(String::as_str), Some(""));
+
+        pool.application_metadata_remove(&app, "key1").unwrap();
+        pool.application_metadata_remove(&app, "key2").unwrap();
+        assert!(pool.application_metadata_get(&app, "key1").is_err());
     }
 }
@@ -1,15 +1,26 @@
 use std::ffi::CString;
+use std::os::raw::c_char;
 use std::ptr::{self};
+use std::sync::{Arc, Condvar, Mutex};
 
+use bytes::Bytes;
 use librados_sys::*;
 
+use crate::buffer::rados_buffer_to_bytes;
 use crate::errors::{self, check_error};
+use crate::utils::c_char_ptr_to_string;
 
 #[derive(Debug)]
 pub(crate) struct Rados {
     pub(crate) ptr: rados_t,
+    // number of `DetachedRados` alive, shutdown waits for it to drop to 0
+    detached: Arc<(Mutex<usize>, Condvar)>,
 }
 
+// librados cluster handles can be used from any thread
+unsafe impl Send for Rados {}
+unsafe impl Sync for Rados {}
+
 impl Rados {
     pub(crate) fn new(
         conf_file: &str,
@@ -40,14 +51,94 @@ impl Rados {
         let code = unsafe { rados_connect(ptr) };
         check_error(code)?;
 
-        Ok(Rados { ptr })
+        Ok(Rados {
+            ptr,
+            detached: Arc::default(),
+        })
+    }
+
+    /// A handle to the cluster for a blocking call on another thread, see
+    /// `utils::blocking`. The connection is only shut down once every such
+    /// handle is dropped, so the call may outlive the future that started it.
+    pub(crate) fn detach(&self) -> DetachedRados {
+        *self.detached.0.lock().unwrap() += 1;
+        DetachedRados {
+            ptr: self.ptr,
+            detached: self.detached.clone(),
+        }
+    }
+
+    pub(crate) fn mon_command(
+        &self,
+        cmd: &str,
+        input: &[u8],
+    ) -> Result<(Bytes, String), errors::Error> {
+        let cmd = CString::new(cmd)?;
+        let mut cmds = [cmd.as_ptr()];
+        let mut outbuf: *mut c_char = ptr::null_mut();
+        let mut outbuf_len: usize = 0;
+        let mut outs: *mut c_char = ptr::null_mut();
+        let mut outs_len: usize = 0;
+
+        let code = unsafe {
+            rados_mon_command(
+                self.ptr,
+                cmds.as_mut_ptr(),
+                cmds.len(),
+                input.as_ptr() as *const c_char,
+                input.len(),
+                &mut outbuf,
+                &mut outbuf_len,
+                &mut outs,
+                &mut outs_len,
+            )
+        };
+
+        let (output, status) = unsafe {
+            let output = rados_buffer_to_bytes(outbuf, outbuf_len);
+            let status = c_char_ptr_to_string(outs, outs_len);
+            if !outs.is_null() {
+                rados_buffer_free(outs);
+            }
+            (output, status)
+        };
+
+        // the status string from the monitor is far more useful than strerror
+        check_error(code).map_err(|e| match e {
+            errors::Error::RadosError((code, _)) if !status.is_empty() => {
+                errors::Error::RadosError((code, status.clone()))
+            }
+            e => e,
+        })?;
+        Ok((output, status))
     }
 }
 
 impl Drop for Rados {
     fn drop(&mut self) {
+        let (count, released) = &*self.detached;
+        let _count = released
+            .wait_while(count.lock().unwrap(), |count| *count > 0)
+            .unwrap();
         unsafe {
             rados_shutdown(self.ptr);
         }
     }
 }
+
+/// A cluster handle owned by a blocking call, see `Rados::detach`.
+pub(crate) struct DetachedRados {
+    pub(crate) ptr: rados_t,
+    detached: Arc<(Mutex<usize>, Condvar)>,
+}
+
+// librados cluster handles can be used from any thread
+unsafe impl Send for DetachedRados {}
+
+impl Drop for DetachedRados {
+    fn drop(&mut self) {
+        let (count, released) = &*self.detached;
+        *count.lock().unwrap() -= 1;
+        released.notify_all();
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::{Display, Formatter};
+use std::time::SystemTime;
+
+/// Id of a pool or self-managed snapshot.
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnapId(u64);
+
+impl SnapId {
+    /// `LIBRADOS_SNAP_HEAD`, the live object rather than a snapshot.
+    pub const HEAD: SnapId = SnapId(u64::MAX - 1);
+    /// `LIBRADOS_SNAP_DIR`, the snapshot directory of an object.
+    pub const DIR: SnapId = SnapId(u64::MAX);
+
+    pub fn new(id: u64) -> Self {
+        SnapId(id)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+
+    pub fn is_head(&self) -> bool {
+        *self == SnapId::HEAD
+    }
+}
+
+impl From<u64> for SnapId {
+    fn from(id: u64) -> Self {
+        SnapId(id)
+    }
+}
+
+impl From<SnapId> for u64 {
+    fn from(id: SnapId) -> Self {
+        id.0
+    }
+}
+
+impl Display for SnapId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            SnapId::HEAD => write!(f, "head"),
+            SnapId::DIR => write!(f, "snapdir"),
+            SnapId(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub id: SnapId,
+    pub name: String,
+    pub created: SystemTime,
+}
+
+/// A version of an object as seen through pool snapshots, see
+/// `Object::snap_versions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapVersion {
+    /// The snapshots that see this version, oldest first.
+    pub snaps: Vec<SnapId>,
+    pub size: u64,
+}
+
+/// How an object differs between two snapshots, see `Pool::diff_snapshots`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ObjectChange {
+    Added,
+    Removed,
+    Size,
+    Mtime,
+    Xattrs,
+    Content,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectDiff {
+    pub name: String,
+    pub change: ObjectChange,
+}
+
+/// The snapshot context writes are tagged with when using self-managed
+/// snapshots: the most recent snapshot id and every snapshot that still exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapContext {
+    seq: SnapId,
+    snaps: Vec<SnapId>,
+}
+
+impl Default for SnapContext {
+    fn default() -> Self {
+        SnapContext {
+            seq: SnapId(0),
+            snaps: Vec::new(),
+        }
+    }
+}
+
+impl SnapContext {
+    pub fn new(seq: SnapId, snaps: &[SnapId]) -> Self {
+        let mut snaps = snaps.to_vec();
+        // librados expects the snapshots sorted from newest to oldest
+        snaps.sort_unstable_by(|a, b| b.cmp(a));
+        snaps.dedup();
+        SnapContext { seq, snaps }
+    }
+
+    pub fn seq(&self) -> SnapId {
+        self.seq
+    }
+
+    pub fn snaps(&self) -> &[SnapId] {
+        &self.snaps
+    }
+
+    /// The context after taking snapshot `snap_id`.
+    pub fn with_snap(&self, snap_id: SnapId) -> Self {
+        let mut snaps = self.snaps.clone();
+        snaps.push(snap_id);
+        SnapContext::new(self.seq.max(snap_id), &snaps)
+    }
+
+    /// The context after removing snapshot `snap_id`.
+    pub fn without_snap(&self, snap_id: SnapId) -> Self {
+        SnapContext {
+            seq: self.seq,
+            snaps: self
+                .snaps
+                .iter()
+                .copied()
+                .filter(|&id| id != snap_id)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_context() {
+        let ids = |ids: &[u64]| ids.iter().map(|&id| SnapId::new(id)).collect::<Vec<_>>();
+
+        let ctx = SnapContext::new(SnapId::new(3), &ids(&[1, 3, 2, 3]));
+        assert_eq!(ctx.seq(), SnapId::new(3));
+        assert_eq!(ctx.snaps(), ids(&[3, 2, 1]));
+
+        let ctx = ctx.with_snap(SnapId::new(5));
+        assert_eq!(ctx.seq(), SnapId::new(5));
+        assert_eq!(ctx.snaps(), ids(&[5, 3, 2, 1]));
+
+        let ctx = ctx.without_snap(SnapId::new(3));
+        assert_eq!(ctx.seq(), SnapId::new(5));
+        assert_eq!(ctx.snaps(), ids(&[5, 2, 1]));
+    }
+
+    #[test]
+    fn test_snap_id() {
+        assert!(SnapId::HEAD.is_head());
+        assert_eq!(SnapId::HEAD.id(), u64::MAX - 1);
+        assert_eq!(SnapId::DIR.id(), u64::MAX);
+        assert_eq!(SnapId::from(4).to_string(), "4");
+        assert_eq!(SnapId::HEAD.to_string(), "head");
+    }
+}
@@ -0,0 +1,675 @@
+//! Objects striped over many RADOS objects, for data larger than
+//! `osd_max_object_size`.
+//!
+//! A striped object `name` is stored as `name.0000000000000000`,
+//! `name.0000000000000001`, ... The first piece always exists and keeps the
+//! layout and the size of the whole in the `striper.layout.*` and
+//! `striper.size` xattrs, as decimal strings. This is the format of
+//! libradosstriper, so `NativeStripedObject`, which implements it on top of
+//! `Object`, and `StripedObject`, which binds libradosstriper itself with the
+//! `striper` feature, can read each other's objects.
+
+#[cfg(feature = "striper")]
+mod radosstriper;
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::anyhow;
+use futures::{stream, StreamExt};
+
+use crate::cls::lock::Lock;
+use crate::errors::{check_error, Error, ERROR_NOT_FOUND};
+use crate::object::{Object, Stat};
+use crate::rados::Rados;
+use crate::snapshot::SnapContext;
+use crate::xattr::{CmpXattrOp, Xattrs};
+
+#[cfg(feature = "striper")]
+pub use radosstriper::StripedObject;
+
+const XATTR_STRIPE_UNIT: &str = "striper.layout.stripe_unit";
+const XATTR_STRIPE_COUNT: &str = "striper.layout.stripe_count";
+const XATTR_OBJECT_SIZE: &str = "striper.layout.object_size";
+const XATTR_SIZE: &str = "striper.size";
+const LOCK_NAME: &str = "striper.lock";
+// where cls_lock keeps the lock state, left behind once unlocked
+const XATTR_LOCK: &str = "lock.striper.lock";
+
+const DEFAULT_MAX_IN_FLIGHT: usize = 16;
+
+// lock holders are the client plus the cookie, so the cookie only has to be
+// unique within this process
+static NEXT_COOKIE: AtomicU64 = AtomicU64::new(0);
+
+/// How a striped object is cut into RADOS objects: data is written
+/// `stripe_unit` bytes at a time round robin over `stripe_count` objects,
+/// each of which holds up to `object_size` bytes before the next set of
+/// objects is started. `object_size` must be a multiple of `stripe_unit`.
+///
+/// The layout is fixed when the striped object is created; later writes use
+/// the layout it was created with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StripeLayout {
+    pub stripe_unit: u32,
+    pub stripe_count: u32,
+    pub object_size: u32,
+}
+
+impl Default for StripeLayout {
+    /// libradosstriper's default: 4 MiB pieces, one object per stripe.
+    fn default() -> Self {
+        StripeLayout {
+            stripe_unit: 4 * 1024 * 1024,
+            stripe_count: 1,
+            object_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+/// Part of a striped extent that falls into one object.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ObjectExtent {
+    object_no: u64,
+    offset: u64,
+    len: u64,
+    // where the part starts within the striped extent
+    buf_offset: u64,
+}
+
+impl StripeLayout {
+    fn validate(&self) -> Result<(), Error> {
+        if self.stripe_unit == 0
+            || self.stripe_count == 0
+            || self.object_size == 0
+            || !self.object_size.is_multiple_of(self.stripe_unit)
+        {
+            return Err(check_error(-22).unwrap_err());
+        }
+        Ok(())
+    }
+
+    fn set_size(&self) -> u64 {
+        self.object_size as u64 * self.stripe_count as u64
+    }
+
+    // split `len` bytes at `offset` of the striped object into object extents,
+    // in striped object order, joining the ones that follow each other
+    fn map_extent(&self, offset: u64, len: u64) -> Vec<ObjectExtent> {
+        let su = self.stripe_unit as u64;
+        let sc = self.stripe_count as u64;
+        let stripes_per_object = self.object_size as u64 / su;
+
+        let mut extents: Vec<ObjectExtent> = Vec::new();
+        let mut pos = offset;
+        while pos < offset + len {
+            let block_no = pos / su;
+            let stripe_no = block_no / sc;
+            let object_set = stripe_no / stripes_per_object;
+            let object_no = object_set * sc + block_no % sc;
+            let object_offset = (stripe_no % stripes_per_object) * su + pos % su;
+            let piece = (su - pos % su).min(offset + len - pos);
+
+            match extents.last_mut() {
+                Some(last)
+                    if last.object_no == object_no && last.offset + last.len == object_offset =>
+                {
+                    last.len += piece;
+                }
+                _ => extents.push(ObjectExtent {
+                    object_no,
+                    offset: object_offset,
+                    len: piece,
+                    buf_offset: pos - offset,
+                }),
+            }
+            pos += piece;
+        }
+        extents
+    }
+
+    // bytes object `object_no` holds in a striped object of `size` bytes
+    fn object_len(&self, object_no: u64, size: u64) -> u64 {
+        let su = self.stripe_unit as u64;
+        let sc = self.stripe_count as u64;
+
+        let set_start = object_no / sc * self.set_size();
+        if size <= set_start {
+            return 0;
+        }
+        let in_set = (size - set_start).min(self.set_size());
+        let full_stripes = in_set / (su * sc);
+        let rest = in_set % (su * sc);
+        let stripe_pos = object_no % sc;
+        full_stripes * su + rest.saturating_sub(stripe_pos * su).min(su)
+    }
+
+    // objects spanned by a striped object of `size` bytes, some of which may
+    // not exist if it was written sparsely
+    fn object_count(&self, size: u64) -> u64 {
+        (size.div_ceil(self.set_size()) * self.stripe_count as u64).max(1)
+    }
+}
+
+/// A striped object implemented on top of `Object`, see the module docs.
+/// Obtained from `Pool::native_striped_object`; no I/O is done until an
+/// operation is called.
+///
+/// The pieces of a request are read and written concurrently. Like
+/// libradosstriper, `truncate` and `remove` hold `striper.lock` exclusively
+/// and fail with `EBUSY` while someone else holds it. Writes don't take the
+/// lock and only ever raise the stored size, conditional on the size they
+/// read, so concurrent writes and truncates never lose a size update. Data of
+/// a write racing a truncate may still land past the new end, so writers
+/// that change the size, through `write_full`, `append` or `truncate`, should
+/// be serialized by the application.
+#[derive(Debug)]
+pub struct NativeStripedObject<'a> {
+    name: String,
+    pool_name: String,
+    rados: &'a Rados,
+    snap_context: Option<SnapContext>,
+    layout: Option<StripeLayout>,
+    max_in_flight: usize,
+}
+
+impl<'a> NativeStripedObject<'a> {
+    pub(crate) fn new(
+        rados: &'a Rados,
+        pool_name: &str,
+        name: &str,
+        snap_context: Option<SnapContext>,
+    ) -> Self {
+        NativeStripedObject {
+            name: name.to_string(),
+            pool_name: pool_name.to_string(),
+            rados,
+            snap_context,
+            layout: None,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Layout to create the object with if a write finds it missing,
+    /// `StripeLayout::default()` unless set.
+    pub fn set_layout(&mut self, layout: StripeLayout) {
+        self.layout = Some(layout);
+    }
+
+    pub fn layout(&self) -> StripeLayout {
+        self.layout.unwrap_or_default()
+    }
+
+    /// Limit the number of pieces read or written at once, 16 by default.
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        self.max_in_flight = max_in_flight.max(1);
+    }
+
+    fn piece(&self, object_no: u64) -> Object<'a> {
+        Object::new(
+            self.rados,
+            &self.pool_name,
+            &format!("{}.{:016x}", self.name, object_no),
+        )
+        .with_snap_context(self.snap_context.clone())
+    }
+
+    // layout and size stored on the first piece
+    async fn open(&self) -> Result<(StripeLayout, u64), Error> {
+        let xattrs = self.piece(0).get_xattrs().await?;
+        let layout = StripeLayout {
+            stripe_unit: parse_xattr(&xattrs, XATTR_STRIPE_UNIT)?,
+            stripe_count: parse_xattr(&xattrs, XATTR_STRIPE_COUNT)?,
+            object_size: parse_xattr(&xattrs, XATTR_OBJECT_SIZE)?,
+        };
+        layout.validate()?;
+        Ok((layout, parse_xattr(&xattrs, XATTR_SIZE)?))
+    }
+
+    async fn open_or_create(&self) -> Result<(StripeLayout, u64), Error> {
+        match self.open().await {
+            Err(e) if e == *ERROR_NOT_FOUND => {}
+            result => return result,
+        }
+
+        let layout = self.layout();
+        layout.validate()?;
+        let stripe_unit = layout.stripe_unit.to_string();
+        let stripe_count = layout.stripe_count.to_string();
+        let object_size = layout.object_size.to_string();
+        let created = self
+            .piece(0)
+            .create_with_xattrs(&[
+                (XATTR_STRIPE_UNIT, stripe_unit.as_bytes()),
+                (XATTR_STRIPE_COUNT, stripe_count.as_bytes()),
+                (XATTR_OBJECT_SIZE, object_size.as_bytes()),
+                (XATTR_SIZE, b"0"),
+            ])
+            .await;
+        match created {
+            Ok(_) => Ok((layout, 0)),
+            // created by someone else in the meantime
+            Err(Error::AlreadyExists(_)) => self.open().await,
+            Err(e) => Err(e),
+        }
+    }
+
+    // raise the stored size to `size` unless it is already larger
+    async fn grow(&self, size: u64) -> Result<(), Error> {
+        self.update_size(|current| (current < size).then_some(size))
+            .await
+            .map(|_| ())
+    }
+
+    // replace the stored size with what `update` makes of it, if anything,
+    // returning the size replaced; conditional on the value read, so
+    // concurrent updates are retried rather than overwritten
+    async fn update_size(&self, update: impl Fn(u64) -> Option<u64>) -> Result<u64, Error> {
+        let first = self.piece(0);
+        loop {
+            let current = first.get_xattr_str(XATTR_SIZE).await?;
+            let size = current
+                .trim_end_matches('\0')
+                .parse()
+                .map_err(|_| Error::OtherError(anyhow!("invalid striper xattr {}", XATTR_SIZE)))?;
+            let Some(new_size) = update(size) else {
+                return Ok(size);
+            };
+            match first
+                .set_xattr_if(
+                    XATTR_SIZE,
+                    CmpXattrOp::Eq,
+                    current.as_bytes(),
+                    new_size.to_string().as_bytes(),
+                )
+                .await
+            {
+                Err(Error::XattrMismatch(_)) => continue,
+                result => return result.map(|_| size),
+            }
+        }
+    }
+
+    // run `op` holding `striper.lock` exclusively, as libradosstriper does to
+    // truncate or remove; the object must exist, taking the lock creates it
+    async fn locked<T>(&self, op: impl Future<Output = Result<T, Error>>) -> Result<T, Error> {
+        let cookie = format!("native-{}", NEXT_COOKIE.fetch_add(1, Ordering::Relaxed));
+        let lock = Lock::exclusive(LOCK_NAME, &cookie);
+        let first = self.piece(0);
+        lock.lock(&first).await?;
+        let result = op.await;
+        match lock.unlock(&first).await {
+            // the lock went with the removed first piece
+            Err(e) if e == *ERROR_NOT_FOUND => result,
+            unlocked => result.and_then(|value| unlocked.map(|_| value)),
+        }
+    }
+
+    // run the futures up to `max_in_flight` at a time, letting all of them
+    // finish before reporting the first error, as they may be borrowing
+    // buffers librados is still using
+    async fn run_all<I>(&self, ops: I) -> Result<(), Error>
+    where
+        I: IntoIterator,
+        I::Item: Future<Output = Result<(), Error>>,
+    {
+        let results: Vec<_> = stream::iter(ops)
+            .buffer_unordered(self.max_in_flight)
+            .collect()
+            .await;
+        results.into_iter().collect()
+    }
+
+    /// Size of the striped object as a whole, mtime of its first piece.
+    pub async fn stat(&self) -> Result<Stat, Error> {
+        let (_, size) = self.open().await?;
+        let stat = self.piece(0).stat().await?;
+        Ok(Stat {
+            size,
+            mtime: stat.mtime,
+        })
+    }
+
+    /// Read into `buf` from `pos`, returning how much was read, which is less
+    /// than `buf.len()` only at the end of the object. Holes left by sparse
+    /// writes read as zeros.
+    pub async fn read(&self, pos: u64, buf: &mut [u8]) -> Result<usize, Error> {
+        let (layout, size) = self.open().await?;
+        if pos >= size {
+            return Ok(0);
+        }
+        let len = (buf.len() as u64).min(size - pos) as usize;
+
+        let mut rest = &mut buf[..len];
+        let mut ops = Vec::new();
+        for extent in layout.map_extent(pos, len as u64) {
+            let (part, tail) = rest.split_at_mut(extent.len as usize);
+            rest = tail;
+            ops.push(async move {
+                let read = match self.piece(extent.object_no).read(extent.offset, part).await {
+                    Ok(read) => read,
+                    Err(e) if e == *ERROR_NOT_FOUND => 0,
+                    Err(e) => return Err(e),
+                };
+                part[read..].fill(0);
+                Ok(())
+            });
+        }
+        self.run_all(ops).await?;
+        Ok(len)
+    }
+
+    /// Write `buf` at `pos`, creating the object with the configured layout
+    /// if it doesn't exist.
+    pub async fn write(&self, pos: u64, buf: &[u8]) -> Result<usize, Error> {
+        let (layout, _) = self.open_or_create().await?;
+
+        let ops = layout
+            .map_extent(pos, buf.len() as u64)
+            .into_iter()
+            .map(|extent| {
+                let start = extent.buf_offset as usize;
+                let part = &buf[start..start + extent.len as usize];
+                async move {
+                    self.piece(extent.object_no)
+                        .write(extent.offset, part)
+                        .await
+                        .map(|_| ())
+                }
+            });
+        self.run_all(ops).await?;
+
+        self.grow(pos + buf.len() as u64).await?;
+        Ok(buf.len())
+    }
+
+    /// Replace the contents with `data`. Not atomic: readers may see the
+    /// object truncated before the new data lands.
+    pub async fn write_full(&self, data: &[u8]) -> Result<usize, Error> {
+        self.open_or_create().await?;
+        self.truncate(0).await?;
+        self.write(0, data).await
+    }
+
+    pub async fn append(&self, data: &[u8]) -> Result<usize, Error> {
+        let (_, size) = self.open_or_create().await?;
+        self.write(size, data).await
+    }
+
+    /// Shrink or extend the object to `size` bytes. Pieces past the new end
+    /// are removed and the one it falls into is truncated.
+    pub async fn truncate(&self, size: u64) -> Result<(), Error> {
+        let (layout, _) = self.open().await?;
+        self.locked(self.truncate_locked(layout, size)).await
+    }
+
+    async fn truncate_locked(&self, layout: StripeLayout, size: u64) -> Result<(), Error> {
+        // shrink the visible size first so readers never see stale data
+        let old_size = self.update_size(|_| Some(size)).await?;
+        if size >= old_size {
+            return Ok(());
+        }
+
+        let ops = (0..layout.object_count(old_size)).filter_map(|object_no| {
+            let old_len = layout.object_len(object_no, old_size);
+            let new_len = layout.object_len(object_no, size);
+            if new_len == old_len {
+                return None;
+            }
+            Some(async move {
+                let piece = self.piece(object_no);
+                let result = if new_len == 0 && object_no != 0 {
+                    piece.remove().await
+                } else {
+                    piece.truncate(new_len).await.map(|_| ())
+                };
+                match result {
+                    Err(e) if e == *ERROR_NOT_FOUND => Ok(()),
+                    result => result,
+                }
+            })
+        });
+        self.run_all(ops).await
+    }
+
+    /// Remove all pieces, the first one, which holds the layout, last.
+    pub async fn remove(&self) -> Result<(), Error> {
+        self.open().await?;
+        self.locked(self.remove_locked()).await
+    }
+
+    async fn remove_locked(&self) -> Result<(), Error> {
+        let (layout, size) = self.open().await?;
+        let ops = (1..layout.object_count(size)).map(|object_no| async move {
+            match self.piece(object_no).remove().await {
+                Err(e) if e == *ERROR_NOT_FOUND => Ok(()),
+                result => result,
+            }
+        });
+        self.run_all(ops).await?;
+        self.piece(0).remove().await
+    }
+
+    /// Get an xattr of the striped object, kept on its first piece.
+    pub async fn get_xattr(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.piece(0).get_xattr(name).await
+    }
+
+    /// The xattrs of the striped object, without the striper's own.
+    pub async fn get_xattrs(&self) -> Result<Xattrs, Error> {
+        let mut xattrs = self.piece(0).get_xattrs().await?;
+        for name in [
+            XATTR_STRIPE_UNIT,
+            XATTR_STRIPE_COUNT,
+            XATTR_OBJECT_SIZE,
+            XATTR_SIZE,
+            XATTR_LOCK,
+        ] {
+            xattrs.remove(name);
+        }
+        Ok(xattrs)
+    }
+
+    pub async fn set_xattr(&self, name: &str, value: &[u8]) -> Result<(), Error> {
+        self.open_or_create().await?;
+        self.piece(0).set_xattr(name, value).await?;
+        Ok(())
+    }
+
+    pub async fn remove_xattr(&self, name: &str) -> Result<(), Error> {
+        self.piece(0).remove_xattr(name).await?;
+        Ok(())
+    }
+}
+
+fn parse_xattr<T: std::str::FromStr>(xattrs: &Xattrs, name: &str) -> Result<T, Error> {
+    // librados reports a missing xattr as ENODATA
+    let value = xattrs
+        .get(name)
+        .ok_or_else(|| check_error(-61).unwrap_err())?;
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.trim_end_matches('\0').parse().ok())
+        .ok_or_else(|| Error::OtherError(anyhow!("invalid striper xattr {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::Pool;
+
+    fn layout(stripe_unit: u32, stripe_count: u32, object_size: u32) -> StripeLayout {
+        StripeLayout {
+            stripe_unit,
+            stripe_count,
+            object_size,
+        }
+    }
+
+    fn extent(object_no: u64, offset: u64, len: u64, buf_offset: u64) -> ObjectExtent {
+        ObjectExtent {
+            object_no,
+            offset,
+            len,
+            buf_offset,
+        }
+    }
+
+    #[test]
+    fn test_map_extent() {
+        // one object per stripe, pieces in the same object are joined
+        let simple = layout(4, 1, 8);
+        assert_eq!(
+            simple.map_extent(2, 12),
+            vec![extent(0, 2, 6, 0), extent(1, 0, 6, 6)]
+        );
+
+        // 3 objects per set, 2 stripe units per object
+        let wide = layout(4, 3, 8);
+        assert_eq!(
+            wide.map_extent(0, 30),
+            vec![
+                extent(0, 0, 4, 0),
+                extent(1, 0, 4, 4),
+                extent(2, 0, 4, 8),
+                extent(0, 4, 4, 12),
+                extent(1, 4, 4, 16),
+                extent(2, 4, 4, 20),
+                extent(3, 0, 4, 24),
+                extent(4, 0, 2, 28),
+            ]
+        );
+        assert_eq!(
+            wide.map_extent(14, 4),
+            vec![extent(0, 6, 2, 0), extent(1, 4, 2, 2)]
+        );
+        assert!(wide.map_extent(5, 0).is_empty());
+    }
+
+    #[test]
+    fn test_object_len() {
+        let wide = layout(4, 3, 8);
+        // the extents of every size agree with object_len
+        for size in 0..60 {
+            let mut lens = vec![0; wide.object_count(size) as usize];
+            for extent in wide.map_extent(0, size) {
+                let len = &mut lens[extent.object_no as usize];
+                *len = (*len).max(extent.offset + extent.len);
+            }
+            for (object_no, len) in lens.iter().enumerate() {
+                assert_eq!(wide.object_len(object_no as u64, size), *len);
+            }
+        }
+        assert_eq!(wide.object_count(0), 1);
+        assert_eq!(wide.object_count(24), 3);
+        assert_eq!(wide.object_count(25), 6);
+        assert!(layout(4, 1, 6).validate().is_err());
+        assert!(StripeLayout::default().validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_native_striped_object() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        let mut obj = pool.native_striped_object("native-striped");
+        let _ = obj.remove().await;
+        obj.set_layout(layout(64 * 1024, 4, 256 * 1024));
+
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 17)
+            .map(|i: u32| (i % 251) as u8)
+            .collect();
+        obj.write_full(&data).await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, data.len() as u64);
+
+        // the layout is stored the way libradosstriper does
+        let first = pool.object("native-striped.0000000000000000");
+        assert_eq!(
+            first.get_xattr_str(XATTR_STRIPE_UNIT).await.unwrap(),
+            "65536"
+        );
+        assert_eq!(
+            first.get_xattr_str(XATTR_SIZE).await.unwrap(),
+            data.len().to_string()
+        );
+        assert!(pool
+            .object("native-striped.000000000000000f")
+            .exists()
+            .await
+            .unwrap());
+
+        let mut buf = vec![0u8; data.len() + 10];
+        assert_eq!(obj.read(0, &mut buf).await.unwrap(), data.len());
+        assert_eq!(&buf[..data.len()], &data[..]);
+
+        obj.append(b"tail").await.unwrap();
+        let mut buf = vec![0u8; 10];
+        assert_eq!(obj.read(data.len() as u64, &mut buf).await.unwrap(), 4);
+        assert_eq!(&buf[..4], b"tail");
+
+        // a sparse write leaves a hole that reads as zeros
+        let end = data.len() as u64 + 4;
+        obj.write(end + 1_000_000, b"far").await.unwrap();
+        let mut buf = vec![1u8; 8];
+        assert_eq!(obj.read(end + 999_995, &mut buf).await.unwrap(), 8);
+        assert_eq!(&buf, b"\0\0\0\0\0far");
+
+        obj.set_xattr("key", b"value").await.unwrap();
+        assert_eq!(obj.get_xattr("key").await.unwrap(), b"value");
+        let xattrs = obj.get_xattrs().await.unwrap();
+        assert!(xattrs.contains_key("key"));
+        assert!(!xattrs.contains_key(XATTR_SIZE));
+        obj.remove_xattr("key").await.unwrap();
+
+        obj.truncate(1000).await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, 1000);
+        assert!(!pool
+            .object("native-striped.0000000000000004")
+            .exists()
+            .await
+            .unwrap());
+        assert!(!obj.get_xattrs().await.unwrap().contains_key(XATTR_LOCK));
+
+        // someone else holding striper.lock, as a libradosstriper writer does
+        let held = Lock::exclusive(LOCK_NAME, "other");
+        held.lock(&first).await.unwrap();
+        let busy = check_error(-16).unwrap_err();
+        assert_eq!(obj.truncate(10).await.unwrap_err(), busy);
+        assert_eq!(obj.remove().await.unwrap_err(), busy);
+        held.unlock(&first).await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, 1000);
+
+        obj.remove().await.unwrap();
+        assert!(!first.exists().await.unwrap());
+    }
+
+    #[cfg(feature = "striper")]
+    #[tokio::test]
+    async fn test_native_striped_object_interop() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let data: Vec<u8> = (0..1024 * 1024 + 5).map(|i: u32| (i % 241) as u8).collect();
+
+        let mut native = pool.native_striped_object("striped-interop");
+        let _ = native.remove().await;
+        native.set_layout(layout(64 * 1024, 3, 128 * 1024));
+        native.write_full(&data).await.unwrap();
+
+        let striped = pool.striped_object("striped-interop");
+        assert_eq!(striped.stat().await.unwrap().size, data.len() as u64);
+        let mut buf = vec![0u8; data.len()];
+        assert_eq!(striped.read(0, &mut buf).await.unwrap(), data.len());
+        assert_eq!(buf, data);
+
+        striped.append(b"more").await.unwrap();
+        let mut buf = vec![0u8; 4];
+        native.read(data.len() as u64, &mut buf).await.unwrap();
+        assert_eq!(&buf, b"more");
+
+        native.remove().await.unwrap();
+    }
+}
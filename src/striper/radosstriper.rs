@@ -0,0 +1,412 @@
+//! Striped objects through libradosstriper itself.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_uint};
+
+use futures::TryFutureExt;
+use librados_sys::*;
+
+use crate::errors::{check_error, Error};
+use crate::io::{AioCompletion, IoCtx};
+use crate::object::Stat;
+use crate::rados::Rados;
+use crate::snapshot::SnapContext;
+use crate::striper::StripeLayout;
+use crate::utils::blocking;
+use crate::xattr::{get_xattr_capped, RadosXattrsIter, Xattrs};
+
+// a striper over its own io context, destroyed before the context
+struct Striper<'a> {
+    ptr: rados_striper_t,
+    _io_ctx: IoCtx<'a>,
+}
+
+impl<'a> Striper<'a> {
+    fn new(io_ctx: IoCtx<'a>, layout: Option<&StripeLayout>) -> Result<Self, Error> {
+        let mut ptr = std::ptr::null_mut();
+        let code = unsafe { rados_striper_create(io_ctx.ptr, &mut ptr) };
+        check_error(code)?;
+        let striper = Striper {
+            ptr,
+            _io_ctx: io_ctx,
+        };
+
+        if let Some(layout) = layout {
+            unsafe {
+                check_error(rados_striper_set_object_layout_stripe_unit(
+                    striper.ptr,
+                    layout.stripe_unit as c_uint,
+                ))?;
+                check_error(rados_striper_set_object_layout_stripe_count(
+                    striper.ptr,
+                    layout.stripe_count as c_uint,
+                ))?;
+                check_error(rados_striper_set_object_layout_object_size(
+                    striper.ptr,
+                    layout.object_size as c_uint,
+                ))?;
+            }
+        }
+        Ok(striper)
+    }
+}
+
+impl Drop for Striper<'_> {
+    fn drop(&mut self) {
+        unsafe { rados_striper_destroy(self.ptr) }
+    }
+}
+
+/// A striped object accessed through libradosstriper. Obtained from
+/// `Pool::striped_object`; no I/O is done until an operation is called.
+#[derive(Debug)]
+pub struct StripedObject<'a> {
+    name: String,
+    pool_name: String,
+    rados: &'a Rados,
+    snap_context: Option<SnapContext>,
+    layout: Option<StripeLayout>,
+    max_xattr_size: usize,
+}
+
+impl<'a> StripedObject<'a> {
+    pub(crate) fn new(
+        rados: &'a Rados,
+        pool_name: &str,
+        name: &str,
+        snap_context: Option<SnapContext>,
+    ) -> Self {
+        StripedObject {
+            name: name.to_string(),
+            pool_name: pool_name.to_string(),
+            rados,
+            snap_context,
+            layout: None,
+            max_xattr_size: usize::MAX,
+        }
+    }
+
+    fn striper(&self) -> Result<Striper<'a>, Error> {
+        let io_ctx = IoCtx::new(self.rados, self.pool_name.clone())?;
+        if let Some(ctx) = &self.snap_context {
+            io_ctx.set_write_ctx(ctx)?;
+        }
+        Striper::new(io_ctx, self.layout.as_ref())
+    }
+
+    // run `f`, which has no aio form, with a striper and the object key on
+    // the blocking thread pool, see `blocking`
+    async fn with_striper<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Striper<'_>, &CStr) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let rados = self.rados.detach();
+        let pool_name = self.pool_name.clone();
+        let (snap_context, layout) = (self.snap_context.clone(), self.layout);
+        let key = self.key()?;
+        blocking(move || {
+            let io_ctx = IoCtx::detached(&rados, pool_name)?;
+            if let Some(ctx) = &snap_context {
+                io_ctx.set_write_ctx(ctx)?;
+            }
+            let striper = Striper::new(io_ctx, layout.as_ref())?;
+            f(&striper, &key)
+        })
+        .await
+    }
+
+    fn key(&self) -> Result<CString, Error> {
+        Ok(CString::new(self.name())?)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Layout to create the object with if a write finds it missing,
+    /// `StripeLayout::default()` unless set.
+    pub fn set_layout(&mut self, layout: StripeLayout) {
+        self.layout = Some(layout);
+    }
+
+    pub fn layout(&self) -> StripeLayout {
+        self.layout.unwrap_or_default()
+    }
+
+    /// Limit the size of xattr values `get_xattr` accepts, see
+    /// `Object::set_max_xattr_size`.
+    pub fn set_max_xattr_size(&mut self, size: usize) {
+        self.max_xattr_size = size;
+    }
+
+    /// Size of the striped object as a whole. The mtime is that of the first
+    /// piece, with second precision.
+    pub async fn stat(&self) -> Result<Stat, Error> {
+        let striper = self.striper()?;
+        let key = self.key()?;
+        let comp = AioCompletion::new()?;
+
+        let mut psize = Box::new(0u64);
+        let mut pmtime: Box<time_t> = Box::new(0);
+
+        let code = unsafe {
+            rados_striper_aio_stat(
+                striper.ptr,
+                key.as_ptr(),
+                comp.ptr,
+                psize.as_mut(),
+                pmtime.as_mut(),
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        comp.await?;
+
+        Ok(Stat {
+            size: *psize,
+            mtime: (*pmtime as u64, 0),
+        })
+    }
+
+    pub async fn read(&self, pos: u64, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let striper = self.striper()?;
+        let key = self.key()?;
+        let comp = AioCompletion::new()?;
+
+        let code = unsafe {
+            rados_striper_aio_read(
+                striper.ptr,
+                key.as_ptr(),
+                comp.ptr,
+                buf.as_mut_ptr() as *mut c_char,
+                buf.len(),
+                pos,
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        comp.await
+    }
+
+    pub async fn write(&self, pos: u64, buf: &[u8]) -> Result<usize, Error> {
+        let striper = self.striper()?;
+        let key = self.key()?;
+        let comp = AioCompletion::new()?;
+
+        let code = unsafe {
+            rados_striper_aio_write(
+                striper.ptr,
+                key.as_ptr(),
+                comp.ptr,
+                buf.as_ptr() as *const c_char,
+                buf.len(),
+                pos,
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        comp.await?;
+        Ok(buf.len())
+    }
+
+    pub async fn write_full(&self, data: &[u8]) -> Result<usize, Error> {
+        let striper = self.striper()?;
+        let key = self.key()?;
+        let comp = AioCompletion::new()?;
+
+        let code = unsafe {
+            rados_striper_aio_write_full(
+                striper.ptr,
+                key.as_ptr(),
+                comp.ptr,
+                data.as_ptr() as *const c_char,
+                data.len(),
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        comp.await?;
+        Ok(data.len())
+    }
+
+    pub async fn append(&self, data: &[u8]) -> Result<usize, Error> {
+        let striper = self.striper()?;
+        let key = self.key()?;
+        let comp = AioCompletion::new()?;
+
+        let code = unsafe {
+            rados_striper_aio_append(
+                striper.ptr,
+                key.as_ptr(),
+                comp.ptr,
+                data.as_ptr() as *const c_char,
+                data.len(),
+            )
+        };
+        assert!(code <= 0);
+        check_error(code)?;
+        comp.await?;
+        Ok(data.len())
+    }
+
+    /// Remove the striped object with all of its pieces.
+    pub async fn remove(&self) -> Result<(), Error> {
+        let striper = self.striper()?;
+        let key = self.key()?;
+        let comp = AioCompletion::new()?;
+
+        let code = unsafe { rados_striper_aio_remove(striper.ptr, key.as_ptr(), comp.ptr) };
+        assert!(code <= 0);
+        check_error(code)?;
+        comp.await?;
+        Ok(())
+    }
+
+    pub async fn truncate(&self, size: u64) -> Result<(), Error> {
+        self.with_striper(move |striper, key| {
+            check_error(unsafe { rados_striper_trunc(striper.ptr, key.as_ptr(), size) })
+        })
+        .await
+    }
+
+    /// Get an xattr value, see `Object::get_xattr`.
+    pub async fn get_xattr(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let (value, ()) = get_xattr_capped(
+            name,
+            self.max_xattr_size,
+            |buf| self.getxattr_into(name, buf).map_ok(|value| (value, ())),
+            || self.get_xattrs().map_ok(|xattrs| (xattrs, ())),
+        )
+        .await?;
+        Ok(value)
+    }
+
+    // read xattr `name` into `buf`, which must be large enough for it
+    async fn getxattr_into(&self, name: &str, mut buf: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let name = CString::new(name)?;
+        self.with_striper(move |striper, key| {
+            let ret = unsafe {
+                rados_striper_getxattr(
+                    striper.ptr,
+                    key.as_ptr(),
+                    name.as_ptr(),
+                    buf.as_mut_ptr() as *mut c_char,
+                    buf.len(),
+                )
+            };
+            check_error(ret)?;
+            buf.truncate(ret as usize);
+            Ok(buf)
+        })
+        .await
+    }
+
+    pub async fn get_xattrs(&self) -> Result<Xattrs, Error> {
+        self.with_striper(|striper, key| {
+            // rados_striper_getxattrs_next and _end forward to their librados
+            // counterparts, so the plain xattr iterator can walk the result
+            let mut iter = RadosXattrsIter::new(std::ptr::null_mut());
+            let code = unsafe { rados_striper_getxattrs(striper.ptr, key.as_ptr(), &mut iter.ptr) };
+            check_error(code)?;
+            Xattrs::from_iter(&iter)
+        })
+        .await
+    }
+
+    pub async fn set_xattr(&self, name: &str, value: &[u8]) -> Result<(), Error> {
+        let name = CString::new(name)?;
+        let value = value.to_vec();
+        self.with_striper(move |striper, key| {
+            check_error(unsafe {
+                rados_striper_setxattr(
+                    striper.ptr,
+                    key.as_ptr(),
+                    name.as_ptr(),
+                    value.as_ptr() as *const c_char,
+                    value.len(),
+                )
+            })
+        })
+        .await
+    }
+
+    pub async fn remove_xattr(&self, name: &str) -> Result<(), Error> {
+        let name = CString::new(name)?;
+        self.with_striper(move |striper, key| {
+            check_error(unsafe { rados_striper_rmxattr(striper.ptr, key.as_ptr(), name.as_ptr()) })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::Pool;
+
+    #[tokio::test]
+    async fn test_striped_object() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+
+        let mut obj = pool.striped_object("striped");
+        let _ = obj.remove().await;
+        let layout = StripeLayout {
+            stripe_unit: 64 * 1024,
+            stripe_count: 4,
+            object_size: 256 * 1024,
+        };
+        obj.set_layout(layout);
+
+        // spans several object sets
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 17)
+            .map(|i: u32| (i % 251) as u8)
+            .collect();
+        obj.write_full(&data).await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, data.len() as u64);
+
+        // the pieces are plain objects named after the striped one
+        let first = pool.object("striped.0000000000000000");
+        assert!(first.exists().await.unwrap());
+        assert!(pool
+            .object("striped.000000000000000f")
+            .exists()
+            .await
+            .unwrap());
+
+        let mut buf = vec![0u8; data.len()];
+        assert_eq!(obj.read(0, &mut buf).await.unwrap(), data.len());
+        assert_eq!(buf, data);
+
+        let mut buf = vec![0u8; 100_000];
+        assert_eq!(obj.read(200_000, &mut buf).await.unwrap(), buf.len());
+        assert_eq!(&buf[..], &data[200_000..300_000]);
+
+        obj.append(b"tail").await.unwrap();
+        let mut buf = vec![0u8; 10];
+        assert_eq!(obj.read(data.len() as u64, &mut buf).await.unwrap(), 4);
+        assert_eq!(&buf[..4], b"tail");
+
+        obj.write(10, b"over").await.unwrap();
+        obj.read(8, &mut buf[..6]).await.unwrap();
+        assert_eq!(&buf[..6], &[data[8], data[9], b'o', b'v', b'e', b'r']);
+
+        obj.set_xattr("key", b"value").await.unwrap();
+        assert_eq!(obj.get_xattr("key").await.unwrap(), b"value");
+        assert!(obj.get_xattrs().await.unwrap().get("key").is_some());
+        obj.remove_xattr("key").await.unwrap();
+        assert!(obj.get_xattr("key").await.is_err());
+
+        obj.truncate(1000).await.unwrap();
+        assert_eq!(obj.stat().await.unwrap().size, 1000);
+
+        obj.remove().await.unwrap();
+        assert!(obj.stat().await.is_err());
+        assert!(!first.exists().await.unwrap());
+    }
+}
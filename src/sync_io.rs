@@ -0,0 +1,301 @@
+//! Blocking `std::io` adapters for objects, for callers without an async
+//! runtime. Both adapters buffer, so small reads and writes don't each cost a
+//! round trip to the OSD.
+
+use std::ffi::CString;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+
+use librados_sys::*;
+
+use crate::buffer::IO_BUF_SIZE;
+use crate::errors::{check_error, Error};
+use crate::io::IoCtx;
+use crate::object::{Object, ReadWrite};
+use crate::utils::seek_target;
+
+fn object_size(io_ctx: &IoCtx, key: &CString) -> io::Result<u64> {
+    let mut size: u64 = 0;
+    let mut mtime: time_t = 0;
+    let code = unsafe { rados_stat(io_ctx.ptr, key.as_ptr(), &mut size, &mut mtime) };
+    check_error(code)?;
+    Ok(size)
+}
+
+/// Buffered `Read`, `BufRead` and `Seek` over an object, or over an object at
+/// a snapshot.
+#[derive(Debug)]
+pub struct ObjectReader<'a, A = ReadWrite> {
+    io_ctx: IoCtx<'a>,
+    key: CString,
+    // object offset of buf[0]
+    buf_offset: u64,
+    buf: Vec<u8>,
+    // read position within buf
+    buf_pos: usize,
+    capacity: usize,
+    _access: PhantomData<A>,
+}
+
+impl<'a, A> ObjectReader<'a, A> {
+    pub fn new(object: &Object<'a, A>) -> Result<Self, Error> {
+        Self::with_capacity(IO_BUF_SIZE, object)
+    }
+
+    pub fn with_capacity(capacity: usize, object: &Object<'a, A>) -> Result<Self, Error> {
+        Ok(ObjectReader {
+            io_ctx: object.io_ctx()?,
+            key: CString::new(object.name())?,
+            buf_offset: 0,
+            buf: Vec::new(),
+            buf_pos: 0,
+            capacity: capacity.max(1),
+            _access: PhantomData,
+        })
+    }
+
+    fn position(&self) -> u64 {
+        self.buf_offset + self.buf_pos as u64
+    }
+
+    fn read_at(&self, offset: u64, out: &mut [u8]) -> io::Result<usize> {
+        let code = unsafe {
+            rados_read(
+                self.io_ctx.ptr,
+                self.key.as_ptr(),
+                out.as_mut_ptr() as *mut c_char,
+                out.len(),
+                offset,
+            )
+        };
+        check_error(code)?;
+        Ok(code as usize)
+    }
+}
+
+impl<A> Read for ObjectReader<'_, A> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        // large reads bypass the buffer once it's drained
+        if self.buf_pos == self.buf.len() && out.len() >= self.capacity {
+            let pos = self.position();
+            let n = self.read_at(pos, out)?;
+            self.buf_offset = pos + n as u64;
+            self.buf.clear();
+            self.buf_pos = 0;
+            return Ok(n);
+        }
+
+        let available = self.fill_buf()?;
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<A> BufRead for ObjectReader<'_, A> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.buf_pos == self.buf.len() {
+            let pos = self.position();
+            let mut buf = std::mem::take(&mut self.buf);
+            buf.resize(self.capacity, 0);
+            let result = self.read_at(pos, &mut buf);
+            buf.truncate(*result.as_ref().unwrap_or(&0));
+            self.buf = buf;
+            self.buf_offset = pos;
+            self.buf_pos = 0;
+            result?;
+        }
+        Ok(&self.buf[self.buf_pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.buf_pos = (self.buf_pos + amt).min(self.buf.len());
+    }
+}
+
+impl<A> Seek for ObjectReader<'_, A> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(offset) => seek_target(self.position(), offset)?,
+            SeekFrom::End(offset) => seek_target(object_size(&self.io_ctx, &self.key)?, offset)?,
+        };
+
+        // keep the buffer if the target lies within it
+        let buf_end = self.buf_offset + self.buf.len() as u64;
+        if target >= self.buf_offset && target <= buf_end {
+            self.buf_pos = (target - self.buf_offset) as usize;
+        } else {
+            self.buf.clear();
+            self.buf_offset = target;
+            self.buf_pos = 0;
+        }
+        Ok(target)
+    }
+}
+
+/// Buffered `Write` and `Seek` over an object. Writes go to the object in
+/// place, starting at offset 0; use `replace` to drop the existing content
+/// first. Buffered data is written on `flush`, on seeks and, ignoring errors,
+/// on drop.
+///
+/// Pools that require alignment (erasure coded pools without overwrites, see
+/// `Pool::requires_alignment`) only take writes at the end of the object, so
+/// there the writer sends whole multiples of the alignment until it is
+/// flushed. Flushing an unaligned tail ends the object: later writes, like
+/// writes after a seek away from the end, fail with `EOPNOTSUPP`.
+#[derive(Debug)]
+pub struct ObjectWriter<'a> {
+    io_ctx: IoCtx<'a>,
+    key: CString,
+    // object offset of buf[0]
+    buf_offset: u64,
+    buf: Vec<u8>,
+    // a multiple of alignment when the pool requires one
+    capacity: usize,
+    alignment: usize,
+}
+
+impl<'a> ObjectWriter<'a> {
+    pub fn new(object: &Object<'a>) -> Result<Self, Error> {
+        Self::with_capacity(IO_BUF_SIZE, object)
+    }
+
+    /// A writer buffering up to `capacity` bytes, rounded up to the pool
+    /// alignment.
+    pub fn with_capacity(capacity: usize, object: &Object<'a>) -> Result<Self, Error> {
+        let alignment = object.alignment()? as usize;
+        Ok(ObjectWriter {
+            io_ctx: object.io_ctx()?,
+            key: CString::new(object.name())?,
+            buf_offset: 0,
+            buf: Vec::new(),
+            capacity: capacity.max(1).next_multiple_of(alignment.max(1)),
+            alignment,
+        })
+    }
+
+    /// A writer over the object truncated to zero length, creating it if
+    /// needed.
+    pub fn replace(object: &Object<'a>) -> Result<Self, Error> {
+        let writer = Self::new(object)?;
+        // a full write rather than a truncate, which aligned pools reject
+        let code = unsafe {
+            rados_write_full(writer.io_ctx.ptr, writer.key.as_ptr(), std::ptr::null(), 0)
+        };
+        check_error(code)?;
+        Ok(writer)
+    }
+
+    fn write_at(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let code = unsafe {
+            rados_write(
+                self.io_ctx.ptr,
+                self.key.as_ptr(),
+                data.as_ptr() as *const c_char,
+                data.len(),
+                offset,
+            )
+        };
+        check_error(code)?;
+        Ok(())
+    }
+
+    fn flush_buf(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.write_at(self.buf_offset, &self.buf)?;
+            self.buf_offset += self.buf.len() as u64;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+impl Write for ObjectWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // only a full buffer is flushed here, which keeps writes aligned
+        if self.buf.len() == self.capacity {
+            self.flush_buf()?;
+        }
+        // too large to be worth buffering
+        if self.buf.is_empty() && data.len() >= self.capacity {
+            let len = data.len() - data.len() % self.alignment.max(1);
+            self.write_at(self.buf_offset, &data[..len])?;
+            self.buf_offset += len as u64;
+            return Ok(len);
+        }
+
+        let len = data.len().min(self.capacity - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()
+    }
+}
+
+impl Seek for ObjectWriter<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.flush_buf()?;
+        self.buf_offset = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(offset) => seek_target(self.buf_offset, offset)?,
+            SeekFrom::End(offset) => seek_target(object_size(&self.io_ctx, &self.key)?, offset)?,
+        };
+        Ok(self.buf_offset)
+    }
+}
+
+impl Drop for ObjectWriter<'_> {
+    fn drop(&mut self) {
+        let _ = self.flush_buf();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::Pool;
+    use crate::rados::Rados;
+
+    #[tokio::test]
+    async fn test_object_reader_writer() {
+        let rados = Rados::new("test/ceph.conf", "ceph", "client.admin").unwrap();
+        let pool = Pool::new(&rados, "test");
+        let obj = pool
+            .put_object("sync-io", b"old content that is long")
+            .await
+            .unwrap();
+
+        let lines: Vec<String> = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        {
+            let mut writer = ObjectWriter::replace(&obj).unwrap();
+            for line in &lines {
+                writer.write_all(line.as_bytes()).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        let expected = lines.concat();
+        assert_eq!(obj.stat().await.unwrap().size, expected.len() as u64);
+
+        let reader = ObjectReader::with_capacity(64, &obj).unwrap();
+        let read: Vec<String> = reader.lines().map(|l| l.unwrap() + "\n").collect();
+        assert_eq!(read, lines);
+
+        let mut reader = ObjectReader::with_capacity(64, &obj).unwrap();
+        let mut tail = String::new();
+        reader.seek(SeekFrom::End(-9)).unwrap();
+        reader.read_to_string(&mut tail).unwrap();
+        assert_eq!(tail, "line 999\n");
+
+        reader.seek(SeekFrom::Start(7)).unwrap();
+        let mut buf = [0u8; 7];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"line 1\n");
+
+        pool.remove_object("sync-io").await.unwrap();
+    }
+}
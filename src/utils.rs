@@ -13,3 +13,61 @@ pub(crate) unsafe fn c_char_ptr_to_string(ptr: *const c_char, len: usize) -> Str
         Err(_) => String::new(),
     }
 }
+
+/// Split a buffer of NUL terminated strings, as filled in by librados list calls.
+/// Empty entries are preserved so that parallel key/value buffers stay aligned.
+pub(crate) fn split_nul_terminated(buf: &[u8]) -> Vec<String> {
+    buf.split_inclusive(|&b| b == 0)
+        .map(|s| String::from_utf8_lossy(s.strip_suffix(&[0]).unwrap_or(s)).into_owned())
+        .collect()
+}
+
+/// Run a librados call that has no aio form. With the `tokio` feature, inside
+/// a tokio runtime of either flavor, the call runs on the runtime's blocking
+/// thread pool, so it takes owned arguments (see `Rados::detach`) and keeps
+/// running if the returned future is dropped. Without the feature the call
+/// runs in place and blocks the caller.
+pub(crate) async fn blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    #[cfg(feature = "tokio")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return match handle.spawn_blocking(f).await {
+            Ok(value) => value,
+            Err(e) => match e.try_into_panic() {
+                Ok(panic) => std::panic::resume_unwind(panic),
+                Err(e) => panic!("blocking librados call did not run: {}", e),
+            },
+        };
+    }
+    f()
+}
+
+/// `blocking` for synchronous APIs, which can't hand the call to another
+/// thread. With the `tokio` feature, on a multi-threaded runtime, the worker
+/// thread hands its other tasks over before the call blocks it.
+pub(crate) fn blocking_in_place<T>(f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "tokio")]
+    {
+        use tokio::runtime::{Handle, RuntimeFlavor};
+
+        if let Ok(handle) = Handle::try_current() {
+            if handle.runtime_flavor() == RuntimeFlavor::MultiThread {
+                return tokio::task::block_in_place(f);
+            }
+        }
+    }
+    f()
+}
+
+/// Resolve a relative seek, rejecting positions before the start of the object.
+pub(crate) fn seek_target(base: u64, offset: i64) -> std::io::Result<u64> {
+    base.checked_add_signed(offset).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
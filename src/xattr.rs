@@ -1,20 +1,52 @@
-use std::collections::hash_map::Iter;
+use std::collections::hash_map::{IntoIter, Iter};
 use std::collections::HashMap;
 use std::ffi::CStr;
+use std::future::Future;
 use std::os::raw::c_char;
 use std::slice;
 
-use librados_sys::{rados_getxattrs_end, rados_getxattrs_next, rados_xattrs_iter_t};
+use librados_sys::{
+    rados_getxattrs_end, rados_getxattrs_next, rados_xattrs_iter_t, LIBRADOS_CMPXATTR_OP_EQ,
+    LIBRADOS_CMPXATTR_OP_GT, LIBRADOS_CMPXATTR_OP_GTE, LIBRADOS_CMPXATTR_OP_LT,
+    LIBRADOS_CMPXATTR_OP_LTE, LIBRADOS_CMPXATTR_OP_NE,
+};
 
-use crate::errors::{check_error, Error};
+use crate::buffer::{MAX_BUF_SIZE, XATTR_INITIAL_SIZE};
+use crate::errors::{check_error, Error, ENODATA, ERROR_RANGE};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Xattrs(HashMap<String, Vec<u8>>);
 
+/// Comparison applied by `Object::set_xattr_if` as `expected <op> current`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpXattrOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl CmpXattrOp {
+    pub(crate) fn as_raw(self) -> u8 {
+        (match self {
+            CmpXattrOp::Eq => LIBRADOS_CMPXATTR_OP_EQ,
+            CmpXattrOp::Ne => LIBRADOS_CMPXATTR_OP_NE,
+            CmpXattrOp::Gt => LIBRADOS_CMPXATTR_OP_GT,
+            CmpXattrOp::Gte => LIBRADOS_CMPXATTR_OP_GTE,
+            CmpXattrOp::Lt => LIBRADOS_CMPXATTR_OP_LT,
+            CmpXattrOp::Lte => LIBRADOS_CMPXATTR_OP_LTE,
+        }) as u8
+    }
+}
+
 pub(crate) struct RadosXattrsIter {
     pub(crate) ptr: rados_xattrs_iter_t,
 }
 
+unsafe impl Send for RadosXattrsIter {}
+
 impl RadosXattrsIter {
     pub(crate) fn new(ptr: rados_xattrs_iter_t) -> Self {
         Self { ptr }
@@ -46,18 +78,18 @@ impl Xattrs {
             assert!(code <= 0);
             check_error(code)?;
 
-            if len == 0 {
+            // a NULL name marks the end, values may legitimately be empty
+            if name_ptr.is_null() {
                 break;
             }
 
-            // TODO; should we free the name_ptr and data_ptr?
-            let name = unsafe {
-                let name = CStr::from_ptr(name_ptr).to_str()?.to_string();
-                name
-            };
-            let data = unsafe {
-                let data = slice::from_raw_parts(data_ptr as *const u8, len).to_vec();
-                data
+            // the name and data belong to the iterator and are freed with it,
+            // so each value is copied out once, into a buffer of its own
+            let name = unsafe { CStr::from_ptr(name_ptr).to_str()?.to_string() };
+            let data = if len == 0 || data_ptr.is_null() {
+                Vec::new()
+            } else {
+                unsafe { slice::from_raw_parts(data_ptr as *const u8, len).to_vec() }
             };
 
             xattrs.0.insert(name, data);
@@ -69,4 +101,92 @@ impl Xattrs {
     pub fn iter(&self) -> Iter<String, Vec<u8>> {
         self.0.iter()
     }
+
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.0.get(name).map(Vec::as_slice)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Vec<u8>> {
+        self.0.remove(name)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl IntoIterator for Xattrs {
+    type Item = (String, Vec<u8>);
+    type IntoIter = IntoIter<String, Vec<u8>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Xattrs {
+    type Item = (&'a String, &'a Vec<u8>);
+    type IntoIter = Iter<'a, String, Vec<u8>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Get xattr `name`, failing with `Error::XattrTooLarge` if it is larger
+/// than `max_size`. `getxattr` reads the value into the buffer it's given,
+/// truncating it to the value, and fails with ERANGE if the value doesn't fit.
+///
+/// The value is first read into a 4 KiB buffer. If it doesn't fit, there is
+/// one more round trip: another `getxattr` with a buffer of `max_size` when
+/// that is small enough to allocate, else `getxattrs`, which returns each
+/// value at its exact size. Both also return a `V`, such as the object
+/// version, which is passed on with the value.
+pub(crate) async fn get_xattr_capped<V, G, GF, A, AF>(
+    name: &str,
+    max_size: usize,
+    mut getxattr: G,
+    getxattrs: A,
+) -> Result<(Vec<u8>, V), Error>
+where
+    G: FnMut(Vec<u8>) -> GF,
+    GF: Future<Output = Result<(Vec<u8>, V), Error>>,
+    A: FnOnce() -> AF,
+    AF: Future<Output = Result<(Xattrs, V), Error>>,
+{
+    let initial = XATTR_INITIAL_SIZE.min(max_size);
+    match getxattr(vec![0; initial]).await {
+        Err(e) if e == *ERROR_RANGE => {}
+        result => return result,
+    }
+
+    let too_large = || Error::XattrTooLarge(name.to_string());
+    if initial == max_size {
+        return Err(too_large());
+    }
+    if max_size <= MAX_BUF_SIZE {
+        return getxattr(vec![0; max_size]).await.map_err(|e| {
+            if e == *ERROR_RANGE {
+                too_large()
+            } else {
+                e
+            }
+        });
+    }
+
+    let (mut xattrs, extra) = getxattrs().await?;
+    match xattrs.remove(name) {
+        Some(value) if value.len() > max_size => Err(too_large()),
+        Some(value) => Ok((value, extra)),
+        // removed in between the two calls
+        None => Err(check_error(-ENODATA).unwrap_err()),
+    }
 }